@@ -1,5 +1,6 @@
-use crate::{lexer::data::{TokenMatch, Token}, error::PangError, ast::rule::Rule};
+use crate::{lexer::{data::{TokenMatch, Token}, unescape}, error::PangError, ast::rule::Rule};
 use lazy_static::lazy_static;
+use regex::Regex;
 use serde::{Serialize, Deserialize};
 
 mod rule;
@@ -8,12 +9,14 @@ mod util;
 
 lazy_static! {
     pub static ref RULE: Rule = Rule::new();
+    static ref LIST_ITEM: Regex = Regex::new(r#""((?:[^"\\]|\\.)*)""#).unwrap();
 }
 
 /// Nodes of the AST
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub enum Node {
     Literal(String, usize),
+    List(Vec<String>, usize),
     Int(i64, usize),
     Float(f64, usize),
     Token(Token, usize),
@@ -28,11 +31,46 @@ pub enum Node {
     }
 }
 
+impl Node {
+    /// Flattens a statement into the sequence of its leafs, the inside of a shell is omitted.
+    pub fn flatten(self) -> Vec<Node> {
+        match self {
+            Node::Statement { variant, context, child } => {
+                let mut nodes = variant.flatten();
+                nodes.append(&mut context.flatten());
+                if let Some(child) = child {
+                    nodes.append(&mut child.flatten());
+                }
+                nodes
+            },
+            Node::Shell { outside, inside: _ } => outside.flatten(),
+            _ => vec![self],
+        }
+    }
+
+    /// Rebuilds a statement from a sequence of leafs, the inverse of [`Node::flatten`].
+    pub fn unflatten(mut nodes: Vec<Node>) -> Option<Node> {
+        match nodes.len() {
+            0 | 1 => nodes.pop(),
+            _ => {
+                let rest = nodes.split_off(2);
+                let context = nodes.pop()?;
+                let variant = nodes.pop()?;
+                Some(Node::Statement { 
+                    variant: Box::new(variant), 
+                    context: Box::new(context), 
+                    child: Self::unflatten(rest).map(Box::new) })
+            },
+        }
+    }
+}
+
 /// TokenMatch to Node
 impl From<TokenMatch> for Node {
     fn from(tm: TokenMatch) -> Self {
         match tm.token {
             Token::Literal => Node::Literal(tm.value, tm.start),
+            Token::List => Node::List(LIST_ITEM.captures_iter(&tm.value).map(|c| unescape(&c[1])).collect(), tm.start),
             Token::Integer => Node::Int(tm.value.parse::<i64>().unwrap(), tm.start),
             Token::Float => Node::Float(tm.value.parse::<f64>().unwrap(), tm.start),
             _ => Node::Token(tm.token, tm.start),
@@ -41,15 +79,25 @@ impl From<TokenMatch> for Node {
 }
 
 /// Builds an AST from source
+/// 
+/// A statement or shell prefixed with `EXPLAIN` becomes the context of an `EXPLAIN` statement.
 pub fn parse(lines: Vec<Vec<TokenMatch>>) -> Result<Vec<Node>, PangError> {
     let mut ast: Vec<Node> = Vec::new();
     let mut shell: Option<Box<Node>> = None;
-    for line in lines {
+    let mut explain: Option<usize> = None;
+    for mut line in lines {
+        let explained = match line.first() {
+            Some(TokenMatch { token: Token::Explain, start, .. }) if shell.is_none() => Some(*start),
+            _ => None,
+        };
+        if explained.is_some() {
+            line.remove(0);
+        }
         let node = parse_node(line)?;
         let shell_or_end = is_shell_or_end(&node);
         if shell.clone().is_some() {
             if shell_or_end {
-                ast.push(*shell.unwrap());
+                ast.push(explain_node(*shell.unwrap(), explain.take()));
                 shell = None;
             } else {
                 shell = match *shell.clone().unwrap() {
@@ -65,30 +113,45 @@ pub fn parse(lines: Vec<Vec<TokenMatch>>) -> Result<Vec<Node>, PangError> {
             }
         } else if shell_or_end {
             shell = Some(Box::new(Node::Shell { outside: Box::new(node), inside: Vec::new() }));
+            explain = explained;
         } else {
-            ast.push(node);
+            ast.push(explain_node(node, explained));
         }
     }
     RULE.check(&ast)?;
     Ok(ast)
 }
 
+/// Wraps a node in an `EXPLAIN` statement, if it was prefixed with one
+fn explain_node(node: Node, explain: Option<usize>) -> Node {
+    match explain {
+        Some(loc) => Node::Statement {
+            variant: Box::new(Node::Token(Token::Explain, loc)),
+            context: Box::new(node),
+            child: None },
+        None => node,
+    }
+}
+
 /// Builds Node structure from single line
+/// 
+/// Tokens are consumed in pairs, a single trailing token is kept as a leaf of the last statement.
 pub fn parse_node(tms: Vec<TokenMatch>) -> Result<Node, PangError> {
     let node = match tms.len() {
+        0 => return Err(PangError::SyntaxError(0)),
+        1 => tms[0].clone().into(),
         2 => {
             Node::Statement { 
                 variant: Box::new(tms.get(0).unwrap().clone().into()), 
                 context: Box::new(tms.get(1).unwrap().clone().into()), 
                 child: None }
         },
-        4 => {
+        _ => {
             Node::Statement { 
                 variant: Box::new(tms.get(0).unwrap().clone().into()), 
                 context: Box::new(tms.get(1).unwrap().clone().into()),
                 child: Some(Box::new(parse_node(tms.split_at(2).1.to_vec())?)) }
         },
-        _ => return Err(PangError::SyntaxError(tms.get(0).unwrap().start))
     };
     Ok(node)
 }
@@ -100,6 +163,7 @@ pub fn is_shell_or_end(node: &Node) -> bool {
                 Node::Token(token, _loc) => match token {
                     Token::Select => true,
                     Token::Template => true,
+                    Token::Alter => true,
                     Token::End => true,
                     _ => false,
                 },
@@ -17,16 +17,96 @@ impl Rule {
 
     fn init_rules() -> Vec<RuleSnippet> {
         let mut rules: Vec<RuleSnippet> = Vec::new();
+        // One or more assignments, a value can be an expression over the fields
+        let set = "SET $s <VALUE|ADD|MUL|APPEND> <$s|$i|$f|DEFAULT>";
+        let sets = format!("{} [{}|<+|-|*|/> <$s|$i|$f>]", set, set);
+        // Comparisons of fields or the instance name, joined by AND and OR
+        let comparisons = [
+            "<$s|NAME> <EQ|NE|LT|GT|LE|GE> <$s|$i|$f>",
+            "<$s|NAME> <LIKE|CONTAINS|MATCHES> $s",
+            "<$s|NAME> STARTS WITH $s",
+        ];
+        let joined = comparisons.map(|c| format!("<AND|OR> {}", c)).join("|");
+        let conditions = comparisons.map(|c| format!("{} [IGNORE CASE|{}]", c, joined));
         rules.push(gr!("QUERY <INSTANCE|TEMPLATE>"));
+        rules.push(gr!("QUERY INSTANCE EXPAND"));
+        for order in ["", " ORDER BY $s", " ORDER BY $s <ASC|DESC>"] {
+            if !order.is_empty() {
+                rules.push(grammar_rule(&format!("QUERY INSTANCE{}", order), false, false));
+            }
+            rules.push(grammar_rule(&format!("QUERY INSTANCE OF $s{}", order), false, false));
+            rules.push(grammar_rule(&format!("QUERY INSTANCE OF $s WITH CHILDREN{}", order), false, false));
+            for condition in &conditions {
+                rules.push(grammar_rule(&format!("QUERY INSTANCE {}{}", condition, order), false, false));
+                rules.push(grammar_rule(&format!("QUERY INSTANCE WHERE {}{}", condition, order), false, false));
+                rules.push(grammar_rule(&format!("QUERY INSTANCE OF $s WHERE {}{}", condition, order), false, false));
+                rules.push(grammar_rule(&format!("QUERY INSTANCE OF $s WITH CHILDREN WHERE {}{}", condition, order), false, false));
+            }
+        }
         rules.push(gr!("QUERY $s FROM <TEMPLATE|INSTANCE>"));
+        rules.push(gr!("QUERY $s FROM INSTANCE EXPAND"));
+        rules.push(gr!("QUERY $s FROM TEMPLATE VERSION $i"));
+        rules.push(gr!("QUERY $s FROM TEMPLATE HISTORY"));
         rules.push(gr!("CREATE $s <TEMPLATE|INSTANCE> $s"));
+        rules.push(grammar_rule(&format!("CREATE $s <TEMPLATE|INSTANCE> $s WITH {}", sets), false, false));
+        rules.push(gr!("CREATE $s <TEMPLATE|INSTANCE> $s IF NOT EXISTS"));
+        rules.push(grammar_rule(&format!("CREATE $s <TEMPLATE|INSTANCE> $s IF NOT EXISTS WITH {}", sets), false, false));
+        rules.push(gr!("UPSERT $s TEMPLATE $s"));
+        rules.push(grammar_rule(&format!("UPSERT $s TEMPLATE $s WITH {}", sets), false, false));
+        rules.push(grammar_rule(&format!("UPDATE INSTANCE OF $s {}", sets), false, false));
+        for condition in &conditions {
+            rules.push(grammar_rule(&format!("UPDATE INSTANCE OF $s {} WHERE {}", sets, condition), false, false));
+        }
         rules.push(gr!("TEMPLATE $s", true));
-        rules.push(gr!("<STRING|INTEGER|FLOAT> $s", false, true));
-        rules.push(gr!("<STRING|INTEGER|FLOAT> $s VALUE <$s|$i|$f>", false, true));
+        rules.push(gr!("TEMPLATE $s EXTENDS $s", true));
+        rules.push(gr!("TEMPLATE $s IF NOT EXISTS", true));
+        rules.push(gr!("TEMPLATE $s EXTENDS $s IF NOT EXISTS", true));
+        // Fields of a template, that can also be added to an existing one
+        let fields = [
+            "<STRING|INTEGER|FLOAT> $s [<MIN|MAX> <$i|$f>|MAXLEN $i|MATCHES $s|NOT EMPTY|UNIQUE|SEARCHABLE|STARTING <$s|$i|$f>]",
+            "<STRING|INTEGER|FLOAT> $s VALUE <$s|$i|$f> [<MIN|MAX> <$i|$f>|MAXLEN $i|MATCHES $s|NOT EMPTY|UNIQUE|SEARCHABLE|STARTING <$s|$i|$f>]",
+            "<STRING|INTEGER|FLOAT> $s AS <$s|$i|$f> [<+|-|*|/> <$s|$i|$f>]",
+            "ENUM $s OF $l",
+            "ENUM $s OF $l VALUE $s",
+            "ENUM $s OF $l STARTING $s",
+            "ENUM $s OF $l VALUE $s STARTING $s",
+            "$s TYPE $s",
+            "$s REF $s",
+            "$s REF $s ON DELETE <RESTRICT|CASCADE>",
+            "$s REF $s ON DELETE SET NULL",
+        ];
+        for field in fields {
+            rules.push(gr!(field, false, true));
+            rules.push(grammar_rule(&format!("ADD {}", field), false, true));
+        }
+        rules.push(gr!("ALTER $s", true));
+        rules.push(gr!("ALTER $s DRY RUN", true));
+        rules.push(gr!("DROP $s", false, true));
+        rules.push(gr!("RENAME $s TO $s", false, true));
+        rules.push(gr!("CHANGE $s TO <STRING|INTEGER|FLOAT>", false, true));
         rules.push(gr!("SELECT $s", true));
-        rules.push(gr!("SET $s VALUE <$s|$i|$f>", false, true));
+        rules.push(gr!("SELECT $s IF REVISION $i", true));
+        for condition in &conditions {
+            rules.push(grammar_rule(&format!("SELECT $s IF {}", condition), true, false));
+        }
+        rules.push(grammar_rule(&format!("{} [<+|-|*|/> <$s|$i|$f>]", set), false, true));
+        rules.push(gr!("GET $s", false, true));
         rules.push(gr!("END $s", false, true));
         rules.push(gr!("DELETE $s FROM <TEMPLATE|INSTANCE>"));
+        rules.push(gr!("DELETE $s FROM <TEMPLATE|INSTANCE> IF EXISTS"));
+        rules.push(gr!("DELETE INSTANCE OF $s CONFIRM"));
+        rules.push(gr!("DELETE INSTANCE OF $s CONFIRM COUNT"));
+        rules.push(gr!("DELETE INSTANCE OF $s"));
+        rules.push(gr!("DELETE INSTANCE OF $s COUNT"));
+        for condition in &conditions {
+            rules.push(grammar_rule(&format!("DELETE INSTANCE OF $s WHERE {}", condition), false, false));
+            rules.push(grammar_rule(&format!("DELETE INSTANCE OF $s WHERE {} COUNT", condition), false, false));
+        }
+        rules.push(gr!("RENAME $s TO $s FROM <TEMPLATE|INSTANCE>"));
+        rules.push(gr!("DESCRIBE $s"));
+        rules.push(gr!("SEARCH $s FOR $s"));
+        rules.push(gr!("CREATE INDEX ON $s $l"));
+        rules.push(gr!("DROP INDEX ON $s $l"));
         rules
     }
 
@@ -39,93 +119,6 @@ impl Rule {
 
     fn check_branch(&self, branch: &Node, pos: &Vec<RuleSnippet>) -> Result<(), PangError> {
         match branch {
-            Node::Literal(_, loc) => return Err(PangError::SyntaxError(*loc)),
-            Node::Int(_, loc) => return Err(PangError::SyntaxError(*loc)),
-            Node::Float(_, loc) => return Err(PangError::SyntaxError(*loc)),
-            Node::Token(_, loc) => return Err(PangError::SyntaxError(*loc)),
-            Node::Statement { 
-                variant, 
-                context, 
-                child } => {
-                    // drain valid rules based on variant
-                    let last_pos = Self::get_node_position(variant);
-                    let variant = Self::node_to_token(variant)?;
-                    let pos: Vec<RuleSnippet> = pos.clone()
-                        .drain_filter(|p| {
-                            let p = match p {
-                                RuleSnippet::Statement(s) => s,
-                                RuleSnippet::Expandable(s) => s,
-                                RuleSnippet::Inner(s) => s,
-                                _ => return false
-                            };
-                            return match &p[0] {
-                                RuleSnippet::Defined(s) => variant == s.clone(),
-                                RuleSnippet::Tuple(s) => s.contains(&variant),
-                                _ => false
-                            }
-                        }).collect();
-                    if pos.len() == 0 {
-                        return Err(PangError::SyntaxError(last_pos))
-                    }
-                    // drain valid rules based on context
-                    let last_pos = Self::get_node_position(context);
-                    let context = Self::node_to_token(context)?;
-                    let mut pos: Vec<RuleSnippet> = pos.clone()
-                        .drain_filter(|p| {
-                            let p = match p {
-                                RuleSnippet::Statement(s) => s,
-                                RuleSnippet::Expandable(s) => s,
-                                RuleSnippet::Inner(s) => s,
-                                _ => return false
-                            };
-                            return match p.get(1).unwrap() {
-                                RuleSnippet::Defined(s) => context == s.clone(),
-                                RuleSnippet::Tuple(s) => s.contains(&context),
-                                _ => false
-                            }
-                        }).collect();
-                    if pos.len() == 0 {
-                        return Err(PangError::SyntaxError(last_pos))
-                    }
-                    let mut to_remove: Vec<usize> = Vec::new();
-                    for (index, p) in pos.iter_mut().enumerate() {
-                        let remove =  match p {
-                            RuleSnippet::Statement(s) => {
-                                s.remove(0);
-                                s.remove(0);
-                                s.len() == 0
-                            },
-                            RuleSnippet::Expandable(s) => {
-                                s.remove(0);
-                                s.remove(0);
-                                s.len() == 0
-                            },
-                            RuleSnippet::Inner(s) => {
-                                s.remove(0);
-                                s.remove(0);
-                                s.len() == 0
-                            },
-                            _ => true
-                        };
-                        if remove {
-                            to_remove.push(index);
-                        }
-                    }
-                    pos = pos.iter().enumerate().filter(|(index, _)| {
-                        !to_remove.contains(index)
-                    }).map(|(_, e)| e.clone()).collect();
-                    // Check child
-                    match child {
-                        Some(child) => {
-                            self.check_branch(child, &pos)?
-                        },
-                        None => {
-                            if pos.len() != 0 {
-                                return Err(PangError::SyntaxError(last_pos))
-                            }
-                        },
-                    };
-                },
             Node::Shell { 
                 outside, 
                 inside } => {
@@ -151,13 +144,86 @@ impl Rule {
                         self.check_branch(statement, &pos_inner)?;
                     }
                 },
+            // The explained statement is checked on its own
+            Node::Statement { variant, context, child: None } if matches!(**variant, Node::Token(Token::Explain, _)) => {
+                    self.check_branch(context, pos)?;
+                },
+            // Statements and leafs are matched as a flat sequence of tokens
+            _ => {
+                    let tokens: Vec<(Token, usize)> = branch.clone().flatten().into_iter()
+                        .map(|node| {
+                            let node = Box::new(node);
+                            Ok((Self::node_to_token(&node)?, Self::get_node_position(&node)))
+                        }).collect::<Result<_, PangError>>()?;
+                    let mut deepest = 0;
+                    for p in pos {
+                        let p = match p {
+                            RuleSnippet::Statement(s) => s,
+                            RuleSnippet::Expandable(s) => s,
+                            RuleSnippet::Inner(s) => s,
+                            _ => continue
+                        };
+                        match Self::match_rule(p, &tokens) {
+                            Ok(()) => return Ok(()),
+                            Err(depth) => deepest = deepest.max(depth),
+                        }
+                    }
+                    // Report the first token no rule could match
+                    let last_pos = tokens.get(deepest).or(tokens.last()).map(|(_, loc)| *loc).unwrap_or(0);
+                    return Err(PangError::SyntaxError(last_pos))
+                },
         };
         Ok(())
     }
 
+    /// Matches a rule against a sequence of tokens.
+    /// 
+    /// On failure the number of tokens, that could be matched is returned.
+    fn match_rule(rule: &[RuleSnippet], tokens: &[(Token, usize)]) -> Result<(), usize> {
+        match rule.first() {
+            None => match tokens.len() {
+                0 => Ok(()),
+                _ => Err(0),
+            },
+            Some(RuleSnippet::Repeat(alternatives)) => {
+                // No more repetitions
+                let mut deepest = match Self::match_rule(&rule[1..], tokens) {
+                    Ok(()) => return Ok(()),
+                    Err(depth) => depth,
+                };
+                // One more repetition of any alternative
+                for alternative in alternatives {
+                    let mut next = alternative.clone();
+                    next.extend_from_slice(rule);
+                    match Self::match_rule(&next, tokens) {
+                        Ok(()) => return Ok(()),
+                        Err(depth) => deepest = deepest.max(depth),
+                    }
+                }
+                Err(deepest)
+            },
+            Some(snippet) => {
+                let token = match tokens.first() {
+                    Some((token, _)) => token,
+                    None => return Err(0),
+                };
+                let matches = match snippet {
+                    RuleSnippet::Defined(s) => token == s,
+                    RuleSnippet::Tuple(s) => s.contains(token),
+                    _ => false
+                };
+                if !matches {
+                    return Err(0)
+                }
+                Self::match_rule(&rule[1..], &tokens[1..]).map_err(|depth| depth + 1)
+            },
+        }
+    }
+
     fn node_to_token(node: &Box<Node>) -> Result<Token, PangError> {
         match &**node {
             Node::Literal(_, _) => Ok(Token::Literal),
+            Node::List(_, _) => Ok(Token::List),
             Node::Int(_, _) => Ok(Token::Integer),
             Node::Float(_, _) => Ok(Token::Float),
             Node::Token(t, _) => Ok(*t),
@@ -169,6 +235,7 @@ impl Rule {
     fn get_node_position(node: &Box<Node>) -> usize {
         match &**node {
             Node::Literal(_, loc) => *loc,
+            Node::List(_, loc) => *loc,
             Node::Int(_, loc) => *loc,
             Node::Float(_, loc) => *loc,
             Node::Token(_, loc) => *loc,
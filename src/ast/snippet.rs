@@ -11,22 +11,50 @@ pub enum RuleSnippet {
     Statement(Vec<RuleSnippet>),
     Defined(Token),
     Tuple(Vec<Token>),
+    /// Any of the alternatives, repeated zero or more times
+    Repeat(Vec<Vec<RuleSnippet>>),
 }
 
 pub fn grammar_rule(rule: &str, expandable: bool, inner: bool) -> RuleSnippet {
+    let repeat_regex = Regex::new(r#"\[([^\]]*)\]"#).unwrap();
     let tuple_regex = Regex::new(r#"<(<*(?:[^><]*|<[^>]*>)*>*)>"#).unwrap();
     let keyword_regex = Regex::new("([a-zA-Z$]+)").unwrap();
     if expandable {
-        RuleSnippet::Expandable(parse(rule.to_string(), &tuple_regex, &keyword_regex))
+        RuleSnippet::Expandable(parse(rule.to_string(), &repeat_regex, &tuple_regex, &keyword_regex))
     } else if inner {
-        RuleSnippet::Inner(parse(rule.to_string(), &tuple_regex, &keyword_regex))
+        RuleSnippet::Inner(parse(rule.to_string(), &repeat_regex, &tuple_regex, &keyword_regex))
     } else {
-        RuleSnippet::Statement(parse(rule.to_string(), &tuple_regex, &keyword_regex))
+        RuleSnippet::Statement(parse(rule.to_string(), &repeat_regex, &tuple_regex, &keyword_regex))
     }
 }
 
-fn parse(mut rule: String, /*optional_regex: &Regex,*/ tuple_regex: &Regex, keyword_regex: &Regex) -> Vec<RuleSnippet> {
+fn parse(mut rule: String, repeat_regex: &Regex, tuple_regex: &Regex, keyword_regex: &Regex) -> Vec<RuleSnippet> {
     let mut map: HashMap<usize, RuleSnippet> = HashMap::new();
+    // repetitions, alternatives are separated by `|` outside of tuples
+    for cap in repeat_regex.captures_iter(&rule) {
+        let index = cap.get(0).unwrap().start();
+        let mut alternatives = Vec::new();
+        let mut depth = 0;
+        let mut current = String::new();
+        for c in cap[1].chars() {
+            match c {
+                '<' => depth += 1,
+                '>' => depth -= 1,
+                '|' if depth == 0 => {
+                    alternatives.push(parse(current.clone(), repeat_regex, tuple_regex, keyword_regex));
+                    current.clear();
+                    continue
+                },
+                _ => {}
+            }
+            current.push(c);
+        }
+        alternatives.push(parse(current, repeat_regex, tuple_regex, keyword_regex));
+        map.insert(index, RuleSnippet::Repeat(alternatives));
+    }
+    // blank out matches instead of removing them, so the following indices stay intact
+    rule = repeat_regex.replace_all(&rule, |c: &regex::Captures| " ".repeat(c[0].len())).to_string();
+
     // tuples
     for cap in tuple_regex.captures_iter(&rule) {
         let index = cap.get(0).unwrap().start();
@@ -36,7 +64,7 @@ fn parse(mut rule: String, /*optional_regex: &Regex,*/ tuple_regex: &Regex, keyw
             .collect::<Vec<Token>>();
         map.insert(index, RuleSnippet::Tuple(types));
     }
-    rule = (&*tuple_regex.replace_all(&rule, "")).to_string();
+    rule = tuple_regex.replace_all(&rule, |c: &regex::Captures| " ".repeat(c[0].len())).to_string();
     
     // keywords
     for cap in keyword_regex.captures_iter(&rule) {
@@ -69,8 +97,81 @@ fn to_token(s: &str) -> Token {
         "STRING" => Token::StringType,
         "INTEGER" => Token::IntegerType,
         "FLOAT" => Token::FloatType,
+        "TYPE" => Token::Type,
+        "GET" => Token::Get,
+        "REF" => Token::Ref,
+        "RESTRICT" => Token::Restrict,
+        "CASCADE" => Token::Cascade,
+        "NULL" => Token::Null,
+        "EXPAND" => Token::Expand,
+        "ENUM" => Token::Enum,
+        "MAXLEN" => Token::MaxLen,
+        "MIN" => Token::Min,
+        "MAX" => Token::Max,
+        "MATCHES" => Token::Matches,
+        "NOT" => Token::Not,
+        "EMPTY" => Token::Empty,
+        "UNIQUE" => Token::Unique,
+        "ALTER" => Token::Alter,
+        "ADD" => Token::Add,
+        "DROP" => Token::Drop,
+        "RENAME" => Token::Rename,
+        "CHANGE" => Token::Change,
+        "DRY" => Token::Dry,
+        "RUN" => Token::Run,
+        "VERSION" => Token::Version,
+        "HISTORY" => Token::History,
+        "EXTENDS" => Token::Extends,
+        "WITH" => Token::With,
+        "CHILDREN" => Token::Children,
+        "DESCRIBE" => Token::Describe,
+        "STARTING" => Token::Starting,
+        "DEFAULT" => Token::Default,
+        "UPSERT" => Token::Upsert,
+        "EXISTS" => Token::Exists,
+        "UPDATE" => Token::Update,
+        "WHERE" => Token::Where,
+        "COUNT" => Token::Count,
+        "CONFIRM" => Token::Confirm,
+        "MUL" => Token::Mul,
+        "APPEND" => Token::Append,
+        "REVISION" => Token::Revision,
+        "LIKE" => Token::Like,
+        "STARTS" => Token::Starts,
+        "CONTAINS" => Token::Contains,
+        "IGNORE" => Token::Ignore,
+        "CASE" => Token::Case,
+        "NAME" => Token::Name,
+        "SEARCHABLE" => Token::Searchable,
+        "SEARCH" => Token::Search,
+        "FOR" => Token::For,
+        "INDEX" => Token::Index,
+        "EXPLAIN" => Token::Explain,
+        "ORDER" => Token::Order,
+        "ASC" => Token::Asc,
+        "DESC" => Token::Desc,
+        "+" => Token::Plus,
+        "-" => Token::Minus,
+        "*" => Token::Star,
+        "/" => Token::Slash,
+        // Comparisons are named, as `<` and `>` enclose tuples
+        "EQ" => Token::Equal,
+        "NE" => Token::NotEqual,
+        "LT" => Token::Less,
+        "GT" => Token::Greater,
+        "LE" => Token::LessEqual,
+        "GE" => Token::GreaterEqual,
+        "OF" => Token::Of,
+        "ON" => Token::On,
+        "TO" => Token::To,
+        "AS" => Token::As,
+        "IF" => Token::If,
+        "AND" => Token::And,
+        "OR" => Token::Or,
+        "BY" => Token::By,
         ";" => Token::ENDL,
         "$s" => Token::Literal,
+        "$l" => Token::List,
         "$i" => Token::Integer,
         "$f" => Token::Float,
         _ => Token::ENDL,
@@ -1,6 +1,6 @@
 use std::{sync::{Arc, atomic::{AtomicBool, Ordering}}, thread::{self, JoinHandle}, time::{Instant}, path::Path};
 use lazy_static::lazy_static;
-use crate::{data::{INSTANCES, TEMPLATES, structure::{Template, Instance}}, plog, pwarn, perr};
+use crate::{data::{INSTANCES, TEMPLATES, HISTORY, structure::{Template, Instance}}, query::backend, plog, pwarn, perr};
 
 lazy_static! {
     static ref SAVE_DIR: String = std::env::var("DIR").unwrap_or("./backup/".to_string());
@@ -33,8 +33,9 @@ impl SaveWorker {
 
     /// Load values from disk into memory
     fn load() {
-        let instances_res = std::fs::read_to_string(format!("{}/instances.json", SAVE_DIR.to_string()));
-        let templates_res = std::fs::read_to_string(format!("{}/templates.json", SAVE_DIR.to_string()));
+        let instances_res = std::fs::read_to_string(format!("{}/instances.json", *SAVE_DIR));
+        let templates_res = std::fs::read_to_string(format!("{}/templates.json", *SAVE_DIR));
+        let history_res = std::fs::read_to_string(format!("{}/history.json", *SAVE_DIR));
 
         match templates_res {
             Ok(json_string) => {
@@ -50,12 +51,28 @@ impl SaveWorker {
             Err(_) => pwarn!("No previous backup file for instances!"),
         }
 
+        match history_res {
+            Ok(json_string) => {
+                match serde_json::from_str::<Vec<Template>>(&json_string) {
+                    Ok(history) => {
+                        let mut mutex = HISTORY.lock().unwrap();
+                        *mutex = history;
+                        plog!("Successfully loaded history backup!")
+                    },
+                    Err(err) => pwarn!("{:?}", err),
+                }
+            },
+            Err(_) => pwarn!("No previous backup file for history!"),
+        }
+
         match instances_res {
             Ok(json_string) => {
                 match serde_json::from_str::<Vec<Instance>>(&json_string) {
                     Ok(instances) => {
                         let mut mutex = INSTANCES.lock().unwrap();
                         *mutex = instances;
+                        drop(mutex);
+                        backend::rebuild_indices();
                         plog!("Successfully loaded instance backup!")
                     },
                     Err(err) => pwarn!("{:?}", err),
@@ -69,8 +86,10 @@ impl SaveWorker {
     fn save() {
         let instances_mutex = INSTANCES.lock().unwrap();
         let templates_mutex = TEMPLATES.lock().unwrap();
-        std::fs::write(format!("{}/instances.json", SAVE_DIR.to_string()), serde_json::to_string_pretty(&*instances_mutex).unwrap()).expect("Failed to write backup.");
-        std::fs::write(format!("{}/templates.json", SAVE_DIR.to_string()), serde_json::to_string_pretty(&*templates_mutex).unwrap()).expect("Failed to write backup.");
+        let history_mutex = HISTORY.lock().unwrap();
+        std::fs::write(format!("{}/instances.json", *SAVE_DIR), serde_json::to_string_pretty(&*instances_mutex).unwrap()).expect("Failed to write backup.");
+        std::fs::write(format!("{}/templates.json", *SAVE_DIR), serde_json::to_string_pretty(&*templates_mutex).unwrap()).expect("Failed to write backup.");
+        std::fs::write(format!("{}/history.json", *SAVE_DIR), serde_json::to_string_pretty(&*history_mutex).unwrap()).expect("Failed to write backup.");
     }
 
     /// Background thread that handles the backups.
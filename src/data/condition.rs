@@ -0,0 +1,172 @@
+use std::cmp::Ordering;
+
+use serde::{Serialize, Deserialize};
+
+use crate::error::PangError;
+
+use super::{serialization::Data, structure::{Instance, Pattern}};
+
+/// A predicate over the fields of an [`Instance`].
+///
+/// A field of `None` is the name of the instance.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub enum Condition {
+    /// Compares a field, nested fields are addressed with a dotted path like `Home.City`.
+    Compare(Option<String>, Comparison, Data),
+    /// Matches a string field against a pattern, that is compiled once from a [`TextMatch`].
+    Text(Option<String>, TextMatch, Pattern),
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum Comparison {
+    EQ,
+    NE,
+    LT,
+    GT,
+    LE,
+    GE,
+}
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum TextMatch {
+    /// SQL pattern, `%` matches any number of characters and `_` a single one.
+    LIKE,
+    STARTS,
+    CONTAINS,
+    /// Regex, that has to match somewhere in the string.
+    MATCHES,
+}
+
+impl Condition {
+    /// Checks the condition, values that can't be compared are a type mismatch.
+    pub fn matches(&self, instance: &Instance, loc: usize) -> Result<bool, PangError> {
+        match self {
+            Condition::Compare(field, comparison, value) => {
+                let ordering = match field {
+                    Some(field) => instance.get(field, loc)?.compare(value),
+                    None => value.as_str().map(|value| instance.name.as_str().cmp(value)),
+                }.ok_or(PangError::TypeMismatch(loc))?;
+                Ok(match comparison {
+                    Comparison::EQ => ordering == Ordering::Equal,
+                    Comparison::NE => ordering != Ordering::Equal,
+                    Comparison::LT => ordering == Ordering::Less,
+                    Comparison::GT => ordering == Ordering::Greater,
+                    Comparison::LE => ordering != Ordering::Greater,
+                    Comparison::GE => ordering != Ordering::Less,
+                })
+            },
+            Condition::Text(field, _, pattern) => match field {
+                Some(field) => Ok(pattern.is_match(instance.get(field, loc)?.as_str().ok_or(PangError::TypeMismatch(loc))?)),
+                None => Ok(pattern.is_match(&instance.name)),
+            },
+            Condition::And(left, right) => Ok(left.matches(instance, loc)? && right.matches(instance, loc)?),
+            Condition::Or(left, right) => Ok(left.matches(instance, loc)? || right.matches(instance, loc)?),
+        }
+    }
+}
+
+impl TextMatch {
+    /// Compiles a pattern to the regex matching it, an invalid `MATCHES` regex is a syntax error.
+    pub fn compile(&self, pattern: &str, ignore_case: bool, loc: usize) -> Result<Pattern, PangError> {
+        let regex = match self {
+            TextMatch::LIKE => like_regex(pattern),
+            TextMatch::STARTS => format!("^{}", regex::escape(pattern)),
+            TextMatch::CONTAINS => regex::escape(pattern),
+            TextMatch::MATCHES => pattern.to_string(),
+        };
+        Pattern::new(&if ignore_case { format!("(?i){}", regex) } else { regex }, loc)
+    }
+}
+
+/// Translates a `LIKE` pattern to an anchored regex
+fn like_regex(pattern: &str) -> String {
+    let mut regex = String::from("(?s)^");
+    for c in pattern.chars() {
+        match c {
+            '%' => regex.push_str(".*"),
+            '_' => regex.push('.'),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    regex
+}
+
+#[cfg(test)]
+mod tests {
+    use linked_hash_map::LinkedHashMap;
+
+    use super::*;
+
+    fn note(name: &str, text: &str, day: i64) -> Instance {
+        let mut data = LinkedHashMap::new();
+        data.insert("Text".to_string(), Data::from(text));
+        data.insert("Day".to_string(), Data::from(day));
+        Instance { name: name.to_string(), template: "NOTE".to_string(), version: 1, revision: 1, data }
+    }
+
+    fn text(field: Option<&str>, text_match: TextMatch, pattern: &str, ignore_case: bool) -> Condition {
+        Condition::Text(field.map(str::to_string), text_match, text_match.compile(pattern, ignore_case, 0).unwrap())
+    }
+
+    #[test]
+    fn like_patterns() {
+        let instance = note("2024-01", "Science Lab", 1);
+        assert!(text(Some("Text"), TextMatch::LIKE, "Science%", false).matches(&instance, 0).unwrap());
+        assert!(!text(Some("Text"), TextMatch::LIKE, "science%", false).matches(&instance, 0).unwrap());
+        assert!(text(Some("Text"), TextMatch::LIKE, "science%", true).matches(&instance, 0).unwrap());
+        // LIKE is anchored and `_` is exactly one character
+        assert!(!text(Some("Text"), TextMatch::LIKE, "Lab", false).matches(&instance, 0).unwrap());
+        assert!(text(None, TextMatch::LIKE, "2024-__", false).matches(&instance, 0).unwrap());
+        assert!(!text(None, TextMatch::LIKE, "2024-_", false).matches(&instance, 0).unwrap());
+    }
+
+    #[test]
+    fn literal_patterns_are_escaped() {
+        let instance = note("a.c", "math 100% (done)", 2);
+        assert!(text(Some("Text"), TextMatch::CONTAINS, "(done)", false).matches(&instance, 0).unwrap());
+        assert!(text(Some("Text"), TextMatch::STARTS, "MATH", true).matches(&instance, 0).unwrap());
+        assert!(!text(Some("Text"), TextMatch::STARTS, "100%", false).matches(&instance, 0).unwrap());
+        assert!(!text(None, TextMatch::LIKE, "a.c", false).matches(&note("abc", "", 0), 0).unwrap());
+        assert!(text(None, TextMatch::LIKE, "a.c", false).matches(&instance, 0).unwrap());
+    }
+
+    #[test]
+    fn regex_patterns() {
+        let instance = note("n", "math 100%", 2);
+        assert!(text(Some("Text"), TextMatch::MATCHES, r"\d+%", false).matches(&instance, 0).unwrap());
+        assert!(matches!(TextMatch::MATCHES.compile("(", false, 7), Err(PangError::SyntaxError(7))));
+        // Only strings can be matched
+        assert!(matches!(text(Some("Day"), TextMatch::STARTS, "1", false).matches(&instance, 3), Err(PangError::TypeMismatch(3))));
+    }
+
+    #[test]
+    fn combined_conditions() {
+        let day = |comparison, value: i64| Condition::Compare(Some("Day".to_string()), comparison, Data::from(value));
+        let condition = Condition::Or(
+            Box::new(Condition::Compare(None, Comparison::EQ, Data::from("2023-12"))),
+            Box::new(Condition::And(Box::new(day(Comparison::GT, 1)), Box::new(text(None, TextMatch::LIKE, "2024-__", false)))),
+        );
+        assert!(condition.matches(&note("2023-12", "", 3), 0).unwrap());
+        assert!(condition.matches(&note("2024-02", "", 2), 0).unwrap());
+        assert!(!condition.matches(&note("2024-01", "", 1), 0).unwrap());
+        assert!(matches!(day(Comparison::LE, 1).matches(&note("x", "", 1), 0), Ok(true)));
+        let mismatch = Condition::Compare(Some("Text".to_string()), Comparison::LT, Data::from(1));
+        assert!(matches!(mismatch.matches(&note("x", "", 1), 5), Err(PangError::TypeMismatch(5))));
+    }
+
+    #[test]
+    fn large_integers_compare_exactly() {
+        // 2^53 + 1 can't be held by a float
+        let instance = note("x", "", 9007199254740993);
+        let day = |comparison, value: Data| Condition::Compare(Some("Day".to_string()), comparison, value).matches(&instance, 0).unwrap();
+        assert!(day(Comparison::GT, Data::from(9007199254740992i64)));
+        assert!(day(Comparison::GT, Data::from(9007199254740992.0)));
+        assert!(!day(Comparison::EQ, Data::from(9007199254740992.0)));
+        assert!(day(Comparison::LT, Data::from(9007199254740994.0)));
+        assert!(day(Comparison::EQ, Data::from(9007199254740993i64)));
+    }
+}
@@ -0,0 +1,107 @@
+use linked_hash_map::LinkedHashMap;
+use serde::{Serialize, Deserialize};
+
+use crate::error::PangError;
+
+use super::{serialization::{Data, DataType}, structure::get_path};
+
+/// An arithmetic expression over the fields of an [`Instance`](super::structure::Instance).
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub enum Expression {
+    /// Value of a field, addressed relative to the object holding the expression.
+    Field(String),
+    Value(Data),
+    Operation(Box<Expression>, Operator, Box<Expression>),
+}
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum Operator {
+    ADD,
+    SUB,
+    MUL,
+    DIV,
+}
+
+impl Expression {
+    /// Every field the expression depends on.
+    pub fn fields(&self) -> Vec<String> {
+        match self {
+            Expression::Field(field) => vec![field.clone()],
+            Expression::Value(_) => Vec::new(),
+            Expression::Operation(left, _, right) => {
+                let mut fields = left.fields();
+                fields.append(&mut right.fields());
+                fields
+            },
+        }
+    }
+
+    /// Renames a field the expression depends on, including its nested fields.
+    pub fn rename(&mut self, from: &str, to: &str) {
+        match self {
+            Expression::Field(field) => {
+                if field == from {
+                    *field = to.to_string();
+                } else if let Some(rest) = field.strip_prefix(&format!("{}.", from)) {
+                    *field = format!("{}.{}", to, rest);
+                }
+            },
+            Expression::Value(_) => {},
+            Expression::Operation(left, _, right) => {
+                left.rename(from, to);
+                right.rename(from, to);
+            },
+        }
+    }
+
+    /// Evaluates the expression, the fields are looked up below the dotted path prefix.
+    pub fn evaluate(&self, data: &LinkedHashMap<String, Data>, prefix: &str, loc: usize) -> Result<Data, PangError> {
+        match self {
+            Expression::Field(field) => get_path(data, &format!("{}{}", prefix, field), loc),
+            Expression::Value(value) => Ok(value.clone()),
+            Expression::Operation(left, operator, right) =>
+                operator.apply(&left.evaluate(data, prefix, loc)?, &right.evaluate(data, prefix, loc)?, loc),
+        }
+    }
+}
+
+impl Operator {
+    /// Applies the operator to two values.
+    ///
+    /// Integers stay integers, as soon as a float is involved the result is a float.
+    /// Strings can only be concatenated with `+`.
+    pub fn apply(&self, left: &Data, right: &Data, loc: usize) -> Result<Data, PangError> {
+        if self == &Operator::DIV && right.as_f64() == Some(0.0) {
+            return Err(PangError::DivisionByZero(loc))
+        }
+        match (left.data_type, right.data_type) {
+            (DataType::INTEGER, DataType::INTEGER) => {
+                let (left, right) = (left.as_i64().unwrap_or_default(), right.as_i64().unwrap_or_default());
+                let res = match self {
+                    Operator::ADD => left.checked_add(right),
+                    Operator::SUB => left.checked_sub(right),
+                    Operator::MUL => left.checked_mul(right),
+                    Operator::DIV => left.checked_div(right),
+                };
+                res.map(Data::from).ok_or(PangError::ArithmeticError(loc))
+            },
+            (DataType::INTEGER | DataType::FLOAT, DataType::INTEGER | DataType::FLOAT) => {
+                let (left, right) = (left.as_f64().unwrap_or_default(), right.as_f64().unwrap_or_default());
+                let res = match self {
+                    Operator::ADD => left + right,
+                    Operator::SUB => left - right,
+                    Operator::MUL => left * right,
+                    Operator::DIV => left / right,
+                };
+                match res.is_finite() {
+                    true => Ok(res.into()),
+                    false => Err(PangError::ArithmeticError(loc)),
+                }
+            },
+            (DataType::STRING, DataType::STRING) if self == &Operator::ADD =>
+                Ok(format!("{}{}", left.as_str().unwrap_or_default(), right.as_str().unwrap_or_default()).into()),
+            _ => Err(PangError::TypeMismatch(loc)),
+        }
+    }
+}
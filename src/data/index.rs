@@ -0,0 +1,262 @@
+use std::{cmp::Ordering, collections::{BTreeMap, BTreeSet, HashMap}, ops::Bound};
+
+use super::{condition::{Comparison, Condition}, serialization::{compare_mixed, Data, DataType}, structure::{get_path, Instance, Template}};
+
+/// Instances found by an index and the indexed fields, that were looked up
+pub type Lookup = (BTreeSet<String>, Vec<String>);
+
+/// Value of an indexed field, integers and floats are ordered together.
+#[derive(Clone, Debug)]
+pub enum IndexKey {
+    /// Integers keep their own key, as floats can't hold them exactly above 2^53.
+    Integer(i64),
+    Float(f64),
+    Text(String),
+}
+
+impl IndexKey {
+    /// Key of a string or numeric value
+    pub fn from_data(data: &Data) -> Option<Self> {
+        match data.data_type {
+            DataType::INTEGER => Some(IndexKey::Integer(data.as_i64()?)),
+            // Adding zero turns -0.0 into 0.0, which are ordered differently
+            DataType::FLOAT => Some(IndexKey::Float(data.as_f64()? + 0.0)),
+            DataType::STRING => Some(IndexKey::Text(data.as_str()?.to_string())),
+            DataType::OBJECT => None,
+        }
+    }
+}
+
+impl Ord for IndexKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (IndexKey::Integer(a), IndexKey::Integer(b)) => a.cmp(b),
+            (IndexKey::Float(a), IndexKey::Float(b)) => a.total_cmp(b),
+            (IndexKey::Integer(a), IndexKey::Float(b)) => compare_mixed(*a, *b),
+            (IndexKey::Float(a), IndexKey::Integer(b)) => compare_mixed(*b, *a).reverse(),
+            (IndexKey::Text(a), IndexKey::Text(b)) => a.cmp(b),
+            (IndexKey::Text(_), _) => Ordering::Greater,
+            (_, IndexKey::Text(_)) => Ordering::Less,
+        }
+    }
+}
+
+impl PartialOrd for IndexKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for IndexKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for IndexKey {}
+
+/// Ordered indexes of the indexed fields of every [`Template`].
+#[derive(Default)]
+pub struct OrderedIndex {
+    /// Maps the template and field name of every indexed field to its values and the instances holding them.
+    fields: HashMap<(String, String), BTreeMap<IndexKey, BTreeSet<String>>>,
+}
+
+impl OrderedIndex {
+    /// Builds the indexes of the given instances
+    pub fn build(instances: &[Instance], templates: &[Template]) -> Self {
+        let mut index = Self::default();
+        for instance in instances {
+            index.index(templates, instance);
+        }
+        index
+    }
+
+    /// Adds the indexed fields of an [`Instance`]
+    pub fn index(&mut self, templates: &[Template], instance: &Instance) {
+        for (key, value) in indexed_values(templates, instance) {
+            self.fields.entry(key).or_default().entry(value).or_default().insert(instance.name.clone());
+        }
+    }
+
+    /// Removes the indexed fields of an [`Instance`]
+    pub fn unindex(&mut self, templates: &[Template], instance: &Instance) {
+        for (key, value) in indexed_values(templates, instance) {
+            let values = match self.fields.get_mut(&key) {
+                Some(values) => values,
+                None => continue,
+            };
+            if let Some(holders) = values.get_mut(&value) {
+                holders.remove(&instance.name);
+                if holders.is_empty() {
+                    values.remove(&value);
+                }
+            }
+        }
+    }
+
+    /// Instances of a [`Template`], whose indexed field lies within the bounds
+    pub fn range(&self, template: &str, field: &str, lower: Bound<&IndexKey>, upper: Bound<&IndexKey>) -> BTreeSet<String> {
+        match self.fields.get(&(template.to_string(), field.to_string())) {
+            Some(values) => values.range::<IndexKey, _>((lower, upper)).flat_map(|(_, holders)| holders.iter().cloned()).collect(),
+            None => BTreeSet::new(),
+        }
+    }
+
+    /// Instances of a [`Template`] ordered by an indexed field, equal values are ordered by name
+    pub fn ordered(&self, template: &str, field: &str) -> Vec<String> {
+        match self.fields.get(&(template.to_string(), field.to_string())) {
+            Some(values) => values.values().flat_map(|holders| holders.iter().cloned()).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Instances of a [`Template`], that can match the condition, if the indexes can narrow it down.
+    ///
+    /// Equality and range comparisons of indexed fields are looked up, the other parts of the condition
+    /// are ignored, so the instances still have to be checked against it.
+    pub fn candidates(&self, template: &Template, condition: &Condition) -> Option<BTreeSet<String>> {
+        self.lookup(template, condition).map(|(candidates, _)| candidates)
+    }
+
+    /// Like [`OrderedIndex::candidates`], but also returns the indexed fields, that were looked up
+    pub fn lookup(&self, template: &Template, condition: &Condition) -> Option<Lookup> {
+        match condition {
+            Condition::Compare(Some(field), comparison, value) => {
+                template.options.get(field).filter(|options| options.indexed)?;
+                let key = IndexKey::from_data(value)?;
+                // Values of another type can't be compared and have to fail the check
+                match (&key, get_path(&template.data, field, 0).ok()?.data_type) {
+                    (IndexKey::Integer(_) | IndexKey::Float(_), DataType::INTEGER | DataType::FLOAT) | (IndexKey::Text(_), DataType::STRING) => {},
+                    _ => return None,
+                }
+                let (lower, upper) = match comparison {
+                    Comparison::EQ => (Bound::Included(&key), Bound::Included(&key)),
+                    Comparison::LT => (Bound::Unbounded, Bound::Excluded(&key)),
+                    Comparison::LE => (Bound::Unbounded, Bound::Included(&key)),
+                    Comparison::GT => (Bound::Excluded(&key), Bound::Unbounded),
+                    Comparison::GE => (Bound::Included(&key), Bound::Unbounded),
+                    Comparison::NE => return None,
+                };
+                Some((self.range(&template.name, field, lower, upper), vec![field.clone()]))
+            },
+            Condition::And(left, right) => match (self.lookup(template, left), self.lookup(template, right)) {
+                (Some((left, mut fields)), Some((right, other))) => {
+                    fields.extend(other);
+                    Some((left.intersection(&right).cloned().collect(), fields))
+                },
+                (Some(lookup), None) | (None, Some(lookup)) => Some(lookup),
+                (None, None) => None,
+            },
+            Condition::Or(left, right) => {
+                let (mut candidates, mut fields) = self.lookup(template, left)?;
+                let (other, other_fields) = self.lookup(template, right)?;
+                candidates.extend(other);
+                fields.extend(other_fields);
+                Some((candidates, fields))
+            },
+            _ => None,
+        }
+    }
+}
+
+/// Values of the indexed fields of an [`Instance`] and their key in the [`OrderedIndex`]
+fn indexed_values(templates: &[Template], instance: &Instance) -> Vec<((String, String), IndexKey)> {
+    let template = match templates.iter().find(|t| t.name == instance.template) {
+        Some(template) => template,
+        None => return Vec::new(),
+    };
+    template.options.iter()
+        .filter(|(_, options)| options.indexed)
+        .filter_map(|(field, _)| {
+            let value = IndexKey::from_data(&instance.get(field, 0).ok()?)?;
+            Some(((instance.template.clone(), field.clone()), value))
+        }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use linked_hash_map::LinkedHashMap;
+
+    use super::*;
+    use crate::data::structure::FieldOptions;
+
+    fn day() -> Template {
+        let mut data = LinkedHashMap::new();
+        data.insert("Day".to_string(), Data::from(0));
+        data.insert("Title".to_string(), Data::from(""));
+        data.insert("Hours".to_string(), Data::from(0.0));
+        let mut options = LinkedHashMap::new();
+        options.insert("Day".to_string(), FieldOptions { indexed: true, ..Default::default() });
+        options.insert("Title".to_string(), FieldOptions { indexed: true, ..Default::default() });
+        Template { name: "DAY".to_string(), version: 1, parent: None, data, options }
+    }
+
+    fn instance(name: &str, day: i64, title: &str) -> Instance {
+        let mut data = LinkedHashMap::new();
+        data.insert("Day".to_string(), Data::from(day));
+        data.insert("Title".to_string(), Data::from(title));
+        data.insert("Hours".to_string(), Data::from(1.0));
+        Instance { name: name.to_string(), template: "DAY".to_string(), version: 1, revision: 1, data }
+    }
+
+    fn compare(field: &str, comparison: Comparison, value: Data) -> Condition {
+        Condition::Compare(Some(field.to_string()), comparison, value)
+    }
+
+    #[test]
+    fn keys_order_integers_exactly() {
+        // 2^53 + 1 can't be held by a float
+        let big = IndexKey::Integer(9007199254740993);
+        assert!(big > IndexKey::Integer(9007199254740992));
+        assert!(big > IndexKey::Float(9007199254740992.0));
+        assert_eq!(IndexKey::Integer(2), IndexKey::Float(2.0));
+        assert!(IndexKey::Integer(2) < IndexKey::Float(2.5));
+        assert!(IndexKey::Integer(-3) < IndexKey::Float(-2.5));
+        assert!(IndexKey::Integer(i64::MAX) < IndexKey::Float(9223372036854775808.0));
+        assert!(IndexKey::Integer(i64::MIN) == IndexKey::Float(-9223372036854775808.0));
+        assert!(IndexKey::Integer(i64::MAX) < IndexKey::Float(f64::NAN));
+        assert!(IndexKey::Integer(i64::MIN) > IndexKey::Float(-f64::NAN));
+        assert!(IndexKey::Text(String::new()) > IndexKey::Integer(i64::MAX));
+        assert_eq!(IndexKey::from_data(&Data::from(-0.0)), IndexKey::from_data(&Data::from(0.0)));
+    }
+
+    #[test]
+    fn lookup_conditions() {
+        let templates = [day()];
+        let instances = [instance("d1", 1, "a"), instance("d2", 2, "b"), instance("d3", 3, "c"), instance("big", 9007199254740993, "b")];
+        let index = OrderedIndex::build(&instances, &templates);
+        let names = |lookup: Option<Lookup>| lookup.map(|(candidates, _)| candidates.into_iter().collect::<Vec<String>>());
+
+        let condition = compare("Day", Comparison::GE, Data::from(2));
+        assert_eq!(names(index.lookup(&templates[0], &condition)), Some(vec!["big".to_string(), "d2".to_string(), "d3".to_string()]));
+        let condition = compare("Day", Comparison::GT, Data::from(9007199254740992));
+        assert_eq!(names(index.lookup(&templates[0], &condition)), Some(vec!["big".to_string()]));
+        let condition = Condition::And(Box::new(compare("Day", Comparison::LT, Data::from(3.5))), Box::new(compare("Title", Comparison::EQ, Data::from("b"))));
+        let (candidates, fields) = index.lookup(&templates[0], &condition).unwrap();
+        assert_eq!(candidates.into_iter().collect::<Vec<String>>(), ["d2"]);
+        assert_eq!(fields, ["Day", "Title"]);
+        let condition = Condition::Or(Box::new(compare("Day", Comparison::EQ, Data::from(1))), Box::new(compare("Title", Comparison::EQ, Data::from("c"))));
+        assert_eq!(names(index.lookup(&templates[0], &condition)), Some(vec!["d1".to_string(), "d3".to_string()]));
+
+        // Not indexed, not comparable or not a range, the instances have to be checked one by one
+        assert_eq!(names(index.lookup(&templates[0], &compare("Hours", Comparison::EQ, Data::from(1.0)))), None);
+        assert_eq!(names(index.lookup(&templates[0], &compare("Title", Comparison::EQ, Data::from(3)))), None);
+        assert_eq!(names(index.lookup(&templates[0], &compare("Day", Comparison::NE, Data::from(1)))), None);
+        let condition = Condition::Or(Box::new(compare("Day", Comparison::EQ, Data::from(1))), Box::new(compare("Hours", Comparison::EQ, Data::from(1.0))));
+        assert_eq!(names(index.lookup(&templates[0], &condition)), None);
+    }
+
+    #[test]
+    fn ordered_and_unindexed() {
+        let templates = [day()];
+        let instances = [instance("d3", 3, "a"), instance("d1", 1, "b"), instance("d2", 2, "b")];
+        let mut index = OrderedIndex::build(&instances, &templates);
+        assert_eq!(index.ordered("DAY", "Day"), ["d1", "d2", "d3"]);
+        assert_eq!(index.ordered("DAY", "Title"), ["d3", "d1", "d2"]);
+        index.unindex(&templates, &instances[1]);
+        assert_eq!(index.ordered("DAY", "Day"), ["d2", "d3"]);
+        assert!(index.range("DAY", "Title", Bound::Included(&IndexKey::Text("b".to_string())), Bound::Unbounded).contains("d2"));
+        assert!(index.ordered("DAY", "Hours").is_empty());
+    }
+}
@@ -1,15 +1,27 @@
-use std::sync::{Arc, Mutex};
+use std::{sync::{Arc, Mutex}, collections::HashMap};
 
 use lazy_static::lazy_static;
 
-use crate::data::structure::Instance;
+use crate::data::{structure::Instance, search::SearchIndex, index::OrderedIndex};
 
 use super::data::structure::Template;
 
 pub mod structure;
 pub mod serialization;
+pub mod expression;
+pub mod condition;
+pub mod search;
+pub mod index;
+
+/// Maps the template and field name of every unique field to its values and the instances holding them.
+pub type UniqueIndex = HashMap<(String, String), HashMap<String, String>>;
 
 lazy_static! {
     pub static ref TEMPLATES:  Arc<Mutex<Vec<Template>>> = Arc::new(Mutex::new(Vec::new()));
     pub static ref INSTANCES:  Arc<Mutex<Vec<Instance>>> = Arc::new(Mutex::new(Vec::new()));
+    /// Every previous definition of the templates
+    pub static ref HISTORY:  Arc<Mutex<Vec<Template>>> = Arc::new(Mutex::new(Vec::new()));
+    pub static ref UNIQUE:  Arc<Mutex<UniqueIndex>> = Arc::new(Mutex::new(HashMap::new()));
+    pub static ref SEARCH:  Arc<Mutex<SearchIndex>> = Arc::new(Mutex::new(SearchIndex::default()));
+    pub static ref INDEXES:  Arc<Mutex<OrderedIndex>> = Arc::new(Mutex::new(OrderedIndex::default()));
 }
\ No newline at end of file
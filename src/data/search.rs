@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+
+use super::structure::{Instance, Template};
+
+/// Inverted index over the searchable string fields of every [`Template`].
+#[derive(Default)]
+pub struct SearchIndex {
+    /// Maps the template name and a term to the instances containing it and the number of occurrences.
+    terms: HashMap<String, HashMap<String, HashMap<String, usize>>>,
+}
+
+impl SearchIndex {
+    /// Builds the index of the given instances
+    pub fn build(instances: &[Instance], templates: &[Template]) -> Self {
+        let mut search = Self::default();
+        for instance in instances {
+            search.index(templates, instance);
+        }
+        search
+    }
+
+    /// Adds the searchable fields of an [`Instance`]
+    pub fn index(&mut self, templates: &[Template], instance: &Instance) {
+        let terms = self.terms.entry(instance.template.clone()).or_default();
+        for (term, count) in instance_terms(templates, instance) {
+            terms.entry(term).or_default().insert(instance.name.clone(), count);
+        }
+    }
+
+    /// Removes the searchable fields of an [`Instance`]
+    pub fn unindex(&mut self, templates: &[Template], instance: &Instance) {
+        let terms = match self.terms.get_mut(&instance.template) {
+            Some(terms) => terms,
+            None => return,
+        };
+        for (term, _) in instance_terms(templates, instance) {
+            if let Some(holders) = terms.get_mut(&term) {
+                holders.remove(&instance.name);
+                if holders.is_empty() {
+                    terms.remove(&term);
+                }
+            }
+        }
+    }
+
+    /// Estimates the number of instances of a template containing any term of the query,
+    /// by adding up the number of instances containing each term.
+    pub fn estimate(&self, template: &str, query: &str) -> usize {
+        let terms = match self.terms.get(template) {
+            Some(terms) => terms,
+            None => return 0,
+        };
+        tokenize(query).iter().filter_map(|term| terms.get(term)).map(|holders| holders.len()).sum()
+    }
+
+    /// Ranks the instances of a template containing any term of the query, the most relevant first.
+    ///
+    /// Terms are weighted by their number of occurrences and how rare they are among the `total` instances.
+    pub fn search(&self, template: &str, query: &str, total: usize) -> Vec<(String, f64)> {
+        let terms = match self.terms.get(template) {
+            Some(terms) => terms,
+            None => return Vec::new(),
+        };
+        let mut scores: HashMap<&String, f64> = HashMap::new();
+        for term in tokenize(query) {
+            let holders = match terms.get(&term) {
+                Some(holders) => holders,
+                None => continue,
+            };
+            let rarity = (1.0 + total as f64 / holders.len() as f64).ln();
+            for (instance, count) in holders {
+                *scores.entry(instance).or_default() += *count as f64 * rarity;
+            }
+        }
+        let mut ranked: Vec<(String, f64)> = scores.into_iter().map(|(name, score)| (name.clone(), score)).collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        ranked
+    }
+}
+
+/// Terms of the searchable fields of an [`Instance`] and their number of occurrences
+fn instance_terms(templates: &[Template], instance: &Instance) -> HashMap<String, usize> {
+    let mut terms = HashMap::new();
+    let template = match templates.iter().find(|t| t.name == instance.template) {
+        Some(template) => template,
+        None => return terms,
+    };
+    for (field, _) in template.options.iter().filter(|(_, options)| options.searchable) {
+        let text = instance.get(field, 0).ok().and_then(|data| data.as_str().map(String::from)).unwrap_or_default();
+        for term in tokenize(&text) {
+            *terms.entry(term).or_default() += 1;
+        }
+    }
+    terms
+}
+
+/// Splits a text into lowercase words
+fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use linked_hash_map::LinkedHashMap;
+
+    use super::*;
+    use crate::data::{serialization::Data, structure::FieldOptions};
+
+    fn day() -> Template {
+        let mut data = LinkedHashMap::new();
+        data.insert("Notes".to_string(), Data::from(""));
+        data.insert("Other".to_string(), Data::from(""));
+        let mut options = LinkedHashMap::new();
+        options.insert("Notes".to_string(), FieldOptions { searchable: true, ..Default::default() });
+        Template { name: "DAY".to_string(), version: 1, parent: None, data, options }
+    }
+
+    fn instance(name: &str, notes: &str, other: &str) -> Instance {
+        let mut data = LinkedHashMap::new();
+        data.insert("Notes".to_string(), Data::from(notes));
+        data.insert("Other".to_string(), Data::from(other));
+        Instance { name: name.to_string(), template: "DAY".to_string(), version: 1, revision: 1, data }
+    }
+
+    fn names(ranked: Vec<(String, f64)>) -> Vec<String> {
+        ranked.into_iter().map(|(name, _)| name).collect()
+    }
+
+    #[test]
+    fn ranks_by_occurrences_and_rarity() {
+        let templates = [day()];
+        let instances = [
+            instance("Mon", "Science lab in the science building", "science"),
+            instance("Tue", "Math and a lab", ""),
+            instance("Wed", "Sport", "science science"),
+        ];
+        let search = SearchIndex::build(&instances, &templates);
+        assert_eq!(names(search.search("DAY", "science lab", 3)), ["Mon", "Tue"]);
+        // Only searchable fields are indexed, the terms are compared in lowercase
+        assert_eq!(names(search.search("DAY", "LAB", 3)), ["Mon", "Tue"]);
+        assert!(search.search("DAY", "sport, math!", 3).iter().all(|(name, _)| name != "Mon"));
+        assert!(search.search("OTHER", "lab", 3).is_empty());
+    }
+
+    #[test]
+    fn rare_terms_weigh_more() {
+        let templates = [day()];
+        let instances = [instance("a", "lab lab", ""), instance("b", "lab", ""), instance("c", "lab rare", "")];
+        let search = SearchIndex::build(&instances, &templates);
+        let ranked = search.search("DAY", "lab rare", 3);
+        assert_eq!(ranked[0].0, "c");
+        assert!(ranked[0].1 > ranked[1].1);
+    }
+
+    #[test]
+    fn unindex_and_estimate() {
+        let templates = [day()];
+        let mon = instance("Mon", "science lab", "");
+        let tue = instance("Tue", "lab", "");
+        let mut search = SearchIndex::build(&[mon.clone(), tue.clone()], &templates);
+        assert_eq!(search.estimate("DAY", "science lab"), 3);
+        search.unindex(&templates, &mon);
+        assert_eq!(names(search.search("DAY", "science lab", 1)), ["Tue"]);
+        assert_eq!(search.estimate("DAY", "science lab"), 1);
+        search.unindex(&templates, &tue);
+        assert_eq!(search.estimate("DAY", "lab"), 0);
+        assert_eq!(search.estimate("NOPE", "lab"), 0);
+    }
+}
@@ -1,24 +1,43 @@
-use serde::{Deserialize, de::{Visitor, self}, Serialize, ser::SerializeStruct};
+use std::{cmp::Ordering, mem::ManuallyDrop};
 
-use crate::query;
+use linked_hash_map::LinkedHashMap;
+use serde::{Deserialize, de::{Visitor, self}, Serialize};
 
-use super::structure::Instance;
+/// Compares an integer with a float exactly, NaN is ordered like [`f64::total_cmp`] does
+pub fn compare_mixed(integer: i64, float: f64) -> Ordering {
+    // 2^63 is the first float above every i64
+    const LIMIT: f64 = 9223372036854775808.0;
+    if float.is_nan() {
+        return if float.is_sign_negative() { Ordering::Greater } else { Ordering::Less }
+    }
+    if float >= LIMIT {
+        return Ordering::Less
+    }
+    if float < -LIMIT {
+        return Ordering::Greater
+    }
+    let truncated = float.trunc();
+    // The fraction decides between an integer and the float truncated to it
+    integer.cmp(&(truncated as i64)).then(truncated.partial_cmp(&float).unwrap_or(Ordering::Equal))
+}
 
+/// Value of a [`Data`], strings and objects are owned and freed when the [`Data`] is dropped.
 pub union DataUnion {
-    pub string: &'static str,
+    pub string: ManuallyDrop<String>,
     pub integer: i64,
     pub float: f64,
+    pub object: ManuallyDrop<Box<LinkedHashMap<String, Data>>>,
 }
 
-impl From<Option<&'static str>> for DataUnion {
-    fn from(string: Option<&'static str>) -> Self {
-        DataUnion { string: string.unwrap_or_default() }
+impl From<Option<&str>> for DataUnion {
+    fn from(string: Option<&str>) -> Self {
+        DataUnion { string: ManuallyDrop::new(string.unwrap_or_default().to_string()) }
     }
 }
 
 impl From<Option<String>> for DataUnion {
     fn from(string: Option<String>) -> Self {
-        DataUnion { string: Box::leak(string.unwrap_or_default().into_boxed_str()) }
+        DataUnion { string: ManuallyDrop::new(string.unwrap_or_default()) }
     }
 }
 
@@ -34,12 +53,20 @@ impl From<Option<f64>> for DataUnion {
     }
 }
 
+impl From<Option<LinkedHashMap<String, Data>>> for DataUnion {
+    fn from(object: Option<LinkedHashMap<String, Data>>) -> Self {
+        DataUnion { object: ManuallyDrop::new(Box::new(object.unwrap_or_default())) }
+    }
+}
+
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
 pub enum DataType {
     STRING,
     INTEGER,
     FLOAT,
+    OBJECT,
 }
 
 /// A wrapper for the [`DataUnion`] union to make it typesafe.
@@ -48,13 +75,92 @@ pub struct Data {
     pub data: DataUnion,
 }
 
+impl Data {
+    /// Returns the string value, if the data is a string.
+    pub fn as_str(&self) -> Option<&str> {
+        match self.data_type {
+            DataType::STRING => Some(unsafe { self.data.string.as_str() }),
+            _ => None,
+        }
+    }
+
+    /// Returns the fields, if the data is an object.
+    pub fn as_object(&self) -> Option<&LinkedHashMap<String, Data>> {
+        match self.data_type {
+            DataType::OBJECT => Some(unsafe { &self.data.object }),
+            _ => None,
+        }
+    }
+
+    /// Returns the integer value, if the data is an integer.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self.data_type {
+            DataType::INTEGER => Some(unsafe { self.data.integer }),
+            _ => None,
+        }
+    }
+
+    /// Returns the numeric value, if the data is an integer or float.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self.data_type {
+            DataType::INTEGER => Some(unsafe { self.data.integer } as f64),
+            DataType::FLOAT => Some(unsafe { self.data.float }),
+            _ => None,
+        }
+    }
+
+    /// Compares two values of the same [`DataType`], integers and floats are compared numerically.
+    ///
+    /// Integers are compared exactly, like an index orders them, as floats can't hold them above 2^53.
+    pub fn compare(&self, other: &Data) -> Option<Ordering> {
+        match (self.data_type, other.data_type) {
+            (DataType::STRING, DataType::STRING) => self.as_str().partial_cmp(&other.as_str()),
+            (DataType::INTEGER, DataType::INTEGER) => unsafe { self.data.integer.partial_cmp(&other.data.integer) },
+            (DataType::INTEGER, DataType::FLOAT) if !other.as_f64()?.is_nan() =>
+                Some(compare_mixed(unsafe { self.data.integer }, other.as_f64()?)),
+            (DataType::FLOAT, DataType::INTEGER) if !self.as_f64()?.is_nan() =>
+                Some(compare_mixed(unsafe { other.data.integer }, self.as_f64()?).reverse()),
+            (DataType::FLOAT, DataType::FLOAT) => self.as_f64()?.partial_cmp(&other.as_f64()?),
+            _ => None,
+        }
+    }
+
+    /// Converts the value to another [`DataType`].
+    ///
+    /// Integers become floats and numbers become their textual representation.
+    /// Floats only become integers, if they have no fractional part, and strings
+    /// only become numbers, if they can be parsed. Objects can't be converted.
+    pub fn convert(&self, data_type: DataType) -> Option<Data> {
+        match (self.data_type, data_type) {
+            (DataType::OBJECT, _) | (_, DataType::OBJECT) => None,
+            (from, to) if from == to => Some(self.clone()),
+            (DataType::INTEGER, DataType::FLOAT) => Some(self.as_f64()?.into()),
+            (DataType::INTEGER | DataType::FLOAT, DataType::STRING) => Some(serde_json::to_string(self).ok()?.into()),
+            (DataType::FLOAT, DataType::INTEGER) => {
+                let float = self.as_f64()?;
+                match float.fract() == 0.0 && float >= i64::MIN as f64 && float < i64::MAX as f64 {
+                    true => Some((float as i64).into()),
+                    false => None,
+                }
+            },
+            (DataType::STRING, DataType::INTEGER) => Some(self.as_str()?.trim().parse::<i64>().ok()?.into()),
+            (DataType::STRING, DataType::FLOAT) => {
+                let float = self.as_str()?.trim().parse::<f64>().ok()?;
+                if float.is_finite() { Some(float.into()) } else { None }
+            },
+            _ => None,
+        }
+    }
+}
+
 impl std::fmt::Debug for Data {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Data").field("value", {
             match self.data_type {
-                DataType::STRING => unsafe { &self.data.string },
+                DataType::STRING => unsafe { &*self.data.string },
                 DataType::INTEGER => unsafe { &self.data.integer },
                 DataType::FLOAT => unsafe { &self.data.float },
+                DataType::OBJECT => unsafe { &**self.data.object },
             }
         }).finish()
     }
@@ -63,9 +169,10 @@ impl std::fmt::Debug for Data {
 impl PartialEq for Data {
     fn eq(&self, other: &Self) -> bool {
         self.data_type == other.data_type && match self.data_type {
-            DataType::STRING => unsafe {self.data.string == other.data.string},
+            DataType::STRING => self.as_str() == other.as_str(),
             DataType::INTEGER => unsafe {self.data.integer == other.data.integer},
             DataType::FLOAT => unsafe {self.data.float == other.data.float},
+            DataType::OBJECT => self.as_object() == other.as_object(),
         }
     }
 }
@@ -73,16 +180,27 @@ impl PartialEq for Data {
 impl Clone for Data {
     fn clone(&self) -> Self {
         let data = match self.data_type {
-            DataType::STRING => unsafe { DataUnion { string: self.data.string } },
+            DataType::STRING => DataUnion::from(self.as_str()),
             DataType::INTEGER => unsafe { DataUnion { integer: self.data.integer } },
             DataType::FLOAT => unsafe { DataUnion { float: self.data.float } },
+            DataType::OBJECT => DataUnion::from(self.as_object().cloned()),
         };
-        Self { data_type: self.data_type.clone(), data }
+        Self { data_type: self.data_type, data }
     }
 }
 
-impl From<&'static str> for Data {
-    fn from(string: &'static str) -> Self {
+impl Drop for Data {
+    fn drop(&mut self) {
+        match self.data_type {
+            DataType::STRING => unsafe { ManuallyDrop::drop(&mut self.data.string) },
+            DataType::OBJECT => unsafe { ManuallyDrop::drop(&mut self.data.object) },
+            DataType::INTEGER | DataType::FLOAT => {},
+        }
+    }
+}
+
+impl From<&str> for Data {
+    fn from(string: &str) -> Self {
         Self {
             data_type: DataType::STRING,
             data: DataUnion::from(Some(string))
@@ -117,6 +235,15 @@ impl From<f64> for Data {
     }
 }
 
+impl From<LinkedHashMap<String, Data>> for Data {
+    fn from(object: LinkedHashMap<String, Data>) -> Self {
+        Self {
+            data_type: DataType::OBJECT,
+            data: DataUnion::from(Some(object))
+        }
+    }
+}
+
 pub struct DATAVisitor;
 
 impl Serialize for Data {
@@ -125,9 +252,10 @@ impl Serialize for Data {
         S: serde::Serializer 
     {
         match self.data_type {
-            DataType::STRING => unsafe { serializer.serialize_str(self.data.string) },
+            DataType::STRING => unsafe { serializer.serialize_str(&self.data.string) },
             DataType::INTEGER => unsafe { serializer.serialize_i64(self.data.integer) },
             DataType::FLOAT => unsafe { serializer.serialize_f64(self.data.float) },
+            DataType::OBJECT => unsafe { self.data.object.serialize(serializer) },
         }
     }
 }
@@ -145,7 +273,7 @@ impl<'de> Visitor<'de> for DATAVisitor {
     type Value = Data;
 
     fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-        formatter.write_str("an integer, float or string")
+        formatter.write_str("an integer, float, string or object")
     }
 
     fn visit_u8<E>(self, v: u8) -> Result<Self::Value, E>
@@ -248,7 +376,6 @@ impl<'de> Visitor<'de> for DATAVisitor {
     where
             E: serde::de::Error, 
     {
-        let v: &'static str = Box::leak(v.to_string().into_boxed_str());
         Ok(Data::from(v))
     }
 
@@ -256,126 +383,17 @@ impl<'de> Visitor<'de> for DATAVisitor {
     where
             E: serde::de::Error, 
     {
-        let v: &'static str = Box::leak(v.into_boxed_str());
         Ok(Data::from(v))
     }
-}
 
-impl Serialize for Instance {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
     where
-        S: serde::Serializer {
-        let mut structure = serializer.serialize_struct("Instance", 3)?;
-        structure.serialize_field("name", &self.name)?;
-        structure.serialize_field("template", &self.template.name)?;
-        structure.serialize_field("data", &self.data)?;
-        structure.end()
-    }
-}
-
-
-impl<'de> Deserialize<'de> for Instance {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-    where
-    D: serde::Deserializer<'de> {
-        enum Field {
-            Name,
-            Template,
-            Data
-        }
-
-        impl<'de> Deserialize<'de> for Field {
-            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-            where
-                D: serde::Deserializer<'de> {
-                struct FieldVisitor;
-
-                impl<'de> Visitor<'de> for FieldVisitor {
-                    type Value = Field;
-
-                    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-                        formatter.write_str("`name`, `template` or `data`.")
-                    }
-
-                    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
-                        where
-                            E: serde::de::Error, {
-                        match v {
-                            "name" => Ok(Field::Name),
-                            "template" => Ok(Field::Template),
-                            "data" => Ok(Field::Data),
-                            _ => Err(de::Error::unknown_field(v, FIELDS))
-                        }
-                    }
-                }
-
-                deserializer.deserialize_identifier(FieldVisitor)
-            }
-        }
-
-        struct InstanceVisitor;
-
-        impl<'de> Visitor<'de> for InstanceVisitor {
-            type Value = Instance;
-
-            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-                formatter.write_str("struct Instance")
-            }
-
-            fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
-                where
-                    A: de::SeqAccess<'de>, {
-                let name = seq.next_element()?
-                    .ok_or_else(|| de::Error::invalid_length(0, &self))?;
-                let template = seq.next_element()?
-                    .ok_or_else(|| de::Error::invalid_length(1, &self))?;
-                let data = seq.next_element()?
-                    .ok_or_else(|| de::Error::invalid_length(2, &self))?;
-                let template = query::backend::copy_template(template, 0).expect("Failed to get Template");
-                Ok(Self::Value {
-                    name, template, data
-                })
-            }
-
-            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
-                where
-                    A: de::MapAccess<'de>, {
-                let mut name = None;
-                let mut template = None;
-                let mut data = None;
-                while let Some(key) = map.next_key()? {
-                    match key {
-                        Field::Name => {
-                            if name.is_some() {
-                                return Err(de::Error::duplicate_field("name"));
-                            }
-                            name = Some(map.next_value()?);
-                        },
-                        Field::Template => {
-                            if template.is_some() {
-                                return Err(de::Error::duplicate_field("template"));
-                            }
-                            template = Some(map.next_value()?);
-                        },
-                        Field::Data => {
-                            if data.is_some() {
-                                return Err(de::Error::duplicate_field("data"));
-                            }
-                            data = Some(map.next_value()?);
-                        }
-                    }
-                }
-                let name = name.ok_or_else(|| de::Error::missing_field("name"))?;
-                let template = template.ok_or_else(|| de::Error::missing_field("template"))?;
-                let template = query::backend::copy_template(template, 0).expect("Failed to get Template");
-                let data = data.ok_or_else(|| de::Error::missing_field("data"))?;
-                Ok(Self::Value {
-                    name, template, data
-                })
-            }
+            A: de::MapAccess<'de>, 
+    {
+        let mut object = LinkedHashMap::new();
+        while let Some((key, value)) = map.next_entry::<String, Data>()? {
+            object.insert(key, value);
         }
-
-        const FIELDS: &'static [&'static str] = &["name", "template", "data"];
-        deserializer.deserialize_struct("Instance", FIELDS, InstanceVisitor)
+        Ok(Data::from(object))
     }
-}
\ No newline at end of file
+}
@@ -1,72 +1,457 @@
+use std::cmp::Ordering;
+
 use linked_hash_map::LinkedHashMap;
-use serde::{Serialize, Deserialize};
+use regex::Regex;
+use serde::{Serialize, Deserialize, de};
 
 use crate::error::PangError;
 
-use super::serialization::{Data};
+use super::{serialization::{Data, DataType}, expression::Expression};
 
 /// The core structure of the in-memory values.
 #[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
 pub struct Template {
     pub name: String,
-    pub data: LinkedHashMap<String, Data>
+    /// Incremented on every alteration, starting at 1.
+    #[serde(default = "first_version")]
+    pub version: usize,
+    /// The [`Template`] the fields were inherited from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+    pub data: LinkedHashMap<String, Data>,
+    #[serde(default, skip_serializing_if = "LinkedHashMap::is_empty")]
+    pub options: LinkedHashMap<String, FieldOptions>,
+}
+
+/// Version of a newly created [`Template`]
+fn first_version() -> usize {
+    1
+}
+
+/// Revision of a newly created [`Instance`]
+fn first_revision() -> u64 {
+    1
 }
 
 impl Template {
     pub fn new(name: String) -> TemplateBuilder {
         TemplateBuilder {
             name: Some(name),
+            parent: None,
             data: None,
+            options: None,
+        }
+    }
+
+    /// Values a new [`Instance`] starts with, the values of the [`Template`] replaced by the starting ones.
+    pub fn starting_data(&self) -> LinkedHashMap<String, Data> {
+        let mut data = self.data.clone();
+        for (name, options) in self.options.iter() {
+            if let Some(starting) = &options.starting {
+                overwrite_path(&mut data, name, starting.clone(), 0).ok();
+            }
+        }
+        data
+    }
+
+    /// Gets the starting value of a field, nested fields are addressed with a dotted path like `Home.City`.
+    pub fn starting(&self, name: &str, loc: usize) -> Result<Data, PangError> {
+        get_path(&self.starting_data(), name, loc)
+    }
+
+    /// Computed fields in the order they have to be evaluated, fails if a field depends on itself.
+    pub fn computed_order(&self, loc: usize) -> Result<Vec<String>, PangError> {
+        let mut order = Vec::new();
+        for name in self.options.keys() {
+            self.visit_computed(name, &mut order, &mut Vec::new(), loc)?;
+        }
+        Ok(order)
+    }
+
+    /// Visits the dependencies of a computed field before the field itself
+    fn visit_computed(&self, name: &String, order: &mut Vec<String>, visiting: &mut Vec<String>, loc: usize) -> Result<(), PangError> {
+        let expression = match self.options.get(name).and_then(|o| o.computed.as_ref()) {
+            Some(expression) => expression,
+            None => return Ok(()),
+        };
+        if order.contains(name) {
+            return Ok(())
+        }
+        if visiting.contains(name) {
+            return Err(PangError::DependencyCycle(name.clone(), loc))
+        }
+        visiting.push(name.clone());
+        for field in expression.fields() {
+            self.visit_computed(&format!("{}{}", object_prefix(name), field), order, visiting, loc)?;
+        }
+        visiting.pop();
+        order.push(name.clone());
+        Ok(())
+    }
+
+    /// Recomputes every computed field, integer results of float fields are converted.
+    pub fn compute(&self, data: &mut LinkedHashMap<String, Data>, loc: usize) -> Result<(), PangError> {
+        for name in self.computed_order(loc)? {
+            let expression = match self.options.get(&name).and_then(|o| o.computed.as_ref()) {
+                Some(expression) => expression,
+                None => continue,
+            };
+            let value = match expression.evaluate(data, object_prefix(&name), loc) {
+                // Like the average of no values, a division by zero is zero instead of failing
+                Err(PangError::DivisionByZero(_)) => Data::from(0),
+                res => res?,
+            };
+            let value = widen(value, get_path(data, &name, loc)?.data_type, loc)?;
+            overwrite_path(data, &name, value, loc)?;
+        }
+        Ok(())
+    }
+}
+
+/// Fits the result of an [`Expression`] to the type of a field, integer results of float fields are converted.
+fn widen(value: Data, data_type: DataType, loc: usize) -> Result<Data, PangError> {
+    match (value.data_type, data_type) {
+        (from, to) if from == to => Ok(value),
+        (DataType::INTEGER, DataType::FLOAT) => value.convert(DataType::FLOAT).ok_or(PangError::TypeMismatch(loc)),
+        _ => Err(PangError::TypeMismatch(loc)),
+    }
+}
+
+/// The dotted path of the object holding a field, including the trailing dot.
+pub fn object_prefix(name: &str) -> &str {
+    match name.rfind('.') {
+        Some(index) => &name[..=index],
+        None => "",
+    }
+}
+
+/// Schema information of a [`Template`] field, that can't be derived from its value.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, Default)]
+pub struct FieldOptions {
+    /// Name of the nested [`Template`], if the field embeds one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+    /// Referenced [`Template`], if the field stores the name of another [`Instance`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reference: Option<Reference>,
+    /// Values a string field is restricted to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allowed: Option<Vec<String>>,
+    /// Smallest value of a numeric field.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min: Option<Data>,
+    /// Largest value of a numeric field.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<Data>,
+    /// Maximum number of characters of a string field.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_len: Option<usize>,
+    /// Regex a string field has to match.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub matches: Option<Pattern>,
+    /// Whether a string field is required to not be empty.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub not_empty: bool,
+    /// Whether no two instances may hold the same value, empty strings are exempt.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub unique: bool,
+    /// Whether the words of a string field are indexed for `SEARCH`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub searchable: bool,
+    /// Whether the field is kept in an ordered index for filters and `ORDER BY`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub indexed: bool,
+    /// Value a new [`Instance`] starts with, instead of the value of the [`Template`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub starting: Option<Data>,
+    /// Expression the value of the field is computed from, computed fields can't be set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub computed: Option<Expression>,
+}
+
+impl FieldOptions {
+    /// Validates a value against the restrictions of the field.
+    pub fn validate(&self, name: &str, data: &Data, loc: usize) -> Result<(), PangError> {
+        let violation = |constraint: String| Err(PangError::ConstraintViolation(name.to_string(), constraint, loc));
+        if let Some(allowed) = &self.allowed {
+            if !allowed.iter().any(|v| Some(v.as_str()) == data.as_str()) {
+                return Err(PangError::ValueNotAllowed(allowed.clone(), loc))
+            }
+        }
+        if let Some(min) = &self.min {
+            if data.compare(min) == Some(Ordering::Less) {
+                return violation(format!("MIN {}", serde_json::to_string(min).unwrap_or_default()))
+            }
+        }
+        if let Some(max) = &self.max {
+            if data.compare(max) == Some(Ordering::Greater) {
+                return violation(format!("MAX {}", serde_json::to_string(max).unwrap_or_default()))
+            }
+        }
+        let string = data.as_str().unwrap_or_default();
+        if let Some(max_len) = self.max_len {
+            if string.chars().count() > max_len {
+                return violation(format!("MAXLEN {}", max_len))
+            }
+        }
+        if let Some(pattern) = &self.matches {
+            if !pattern.is_match(string) {
+                return violation(format!("MATCHES {:?}", pattern.as_str()))
+            }
+        }
+        if self.not_empty && string.is_empty() {
+            return violation("NOT EMPTY".to_string())
+        }
+        Ok(())
+    }
+}
+
+/// A regex, that is compiled once and stored as its pattern.
+#[derive(Clone, Debug)]
+pub struct Pattern(Regex);
+
+impl Pattern {
+    /// Compiles a pattern, an invalid one is a syntax error at its location.
+    pub fn new(pattern: &str, loc: usize) -> Result<Self, PangError> {
+        Regex::new(pattern).map(Pattern).map_err(|_| PangError::SyntaxError(loc))
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        self.0.is_match(text)
+    }
+}
+
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Serialize for Pattern {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Pattern {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de> {
+        let pattern = String::deserialize(deserializer)?;
+        Regex::new(&pattern).map(Pattern).map_err(de::Error::custom)
+    }
+}
+
+/// A reference to the instances of a [`Template`].
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Reference {
+    pub template: String,
+    pub on_delete: OnDelete,
+}
+
+/// What happens to a referencing [`Instance`], when the referenced one is deleted.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum OnDelete {
+    /// The deletion is rejected.
+    RESTRICT,
+    /// The referencing instance is deleted as well.
+    CASCADE,
+    /// The reference is cleared.
+    NULL,
+}
+
+/// The schema of a [`Template`] and the number of its instances.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Description {
+    pub name: String,
+    pub version: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+    /// Every field, nested fields follow the field embedding them with a dotted path.
+    pub fields: Vec<FieldDescription>,
+    pub instances: usize,
+}
+
+/// The schema of a single [`Template`] field.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct FieldDescription {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub data_type: DataType,
+    pub default: Data,
+    pub options: FieldOptions,
+}
+
+impl Description {
+    pub fn new(template: &Template, instances: usize) -> Self {
+        let mut fields = Vec::new();
+        describe_fields(&mut fields, template, &template.data, "");
+        Self {
+            name: template.name.clone(),
+            version: template.version,
+            parent: template.parent.clone(),
+            fields,
+            instances,
+        }
+    }
+}
+
+/// Describes the fields of an object at a dotted path prefix.
+fn describe_fields(fields: &mut Vec<FieldDescription>, template: &Template, data: &LinkedHashMap<String, Data>, prefix: &str) {
+    for (field, default) in data.iter() {
+        let name = format!("{}{}", prefix, field);
+        fields.push(FieldDescription {
+            name: name.clone(),
+            data_type: default.data_type,
+            default: default.clone(),
+            options: template.options.get(&name).cloned().unwrap_or_default(),
+        });
+        if let Some(object) = default.as_object() {
+            describe_fields(fields, template, object, &format!("{}.", name));
         }
     }
 }
 
 /// The core structure of the in-memory values.
-#[derive(Clone, PartialEq, Debug)]
+/// 
+/// The [`Template`] is referenced by its name and resolved through the store.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
 pub struct Instance {
     pub name: String,
-    //#[serde(skip_serializing)]
-    pub template: Template,
+    pub template: String,
+    /// Version of the [`Template`] the instance was last validated against.
+    #[serde(default = "first_version")]
+    pub version: usize,
+    /// Increased by every change of the stored instance.
+    #[serde(default = "first_revision")]
+    pub revision: u64,
     pub data: LinkedHashMap<String, Data>
 }
 
 impl Instance {
-    pub fn new(name: String, template: Template) -> Self {
+    pub fn new(name: String, template: &Template) -> Self {
         Self {
-            data: template.data.clone(),
+            data: template.starting_data(),
             name,
-            template,
+            template: template.name.clone(),
+            version: template.version,
+            revision: first_revision(),
         }
     }
 
-    pub fn overwrite(&mut self, name: String, data: Data, loc: usize) -> Result<(), PangError> {
-        match self.data.get_mut(&name) {
-            Some(d) => {
-                if d.data_type != data.data_type {
-                    return Err(PangError::TypeMismatch(loc))
-                }
-                d.data = data.data;
-                Ok(())
-            },
-            None => Err(PangError::DataNonExistent(loc)),
-        }   
-        
+    /// Overwrites a field, nested fields are addressed with a dotted path like `Home.City`.
+    pub fn overwrite(&mut self, template: &Template, name: String, data: Data, loc: usize) -> Result<(), PangError> {
+        if let Some(options) = template.options.get(&name) {
+            if options.computed.is_some() {
+                return Err(PangError::ComputedField(name, loc))
+            }
+            options.validate(&name, &data, loc)?;
+        }
+        overwrite_path(&mut self.data, &name, data, loc)?;
+        self.version = template.version;
+        Ok(())
+    }
+
+    /// Overwrites a field with the result of an [`Expression`], that can reference any field by its dotted path.
+    pub fn assign(&mut self, template: &Template, name: String, expression: &Expression, loc: usize) -> Result<(), PangError> {
+        let data = match expression {
+            Expression::Value(data) => data.clone(),
+            _ => widen(expression.evaluate(&self.data, "", loc)?, self.get(&name, loc)?.data_type, loc)?,
+        };
+        self.overwrite(template, name, data, loc)
+    }
+
+    /// Clears a string field without checking its restrictions, like a reference to a deleted [`Instance`].
+    pub fn clear(&mut self, name: &str, loc: usize) -> Result<(), PangError> {
+        overwrite_path(&mut self.data, name, "".into(), loc)
+    }
+
+    /// Validates every field against the restrictions of the [`Template`].
+    pub fn validate(&self, template: &Template, loc: usize) -> Result<(), PangError> {
+        for (name, options) in template.options.iter() {
+            options.validate(name, &self.get(name, loc)?, loc)?;
+        }
+        Ok(())
+    }
+
+    /// Gets a field, nested fields are addressed with a dotted path like `Home.City`.
+    pub fn get(&self, name: &str, loc: usize) -> Result<Data, PangError> {
+        get_path(&self.data, name, loc)
+    }
+}
+
+/// Overwrites the value at a dotted path, rebuilding every nested object on the way.
+fn overwrite_path(map: &mut LinkedHashMap<String, Data>, path: &str, data: Data, loc: usize) -> Result<(), PangError> {
+    if let Some(d) = map.get_mut(path) {
+        if d.data_type != data.data_type {
+            return Err(PangError::TypeMismatch(loc))
+        }
+        *d = data;
+        return Ok(())
+    }
+    let (field, rest) = path.split_once('.').ok_or(PangError::DataNonExistent(loc))?;
+    match map.get_mut(field) {
+        Some(d) if d.data_type == DataType::OBJECT => {
+            let mut object = d.as_object().cloned().unwrap_or_default();
+            overwrite_path(&mut object, rest, data, loc)?;
+            *d = object.into();
+            Ok(())
+        },
+        _ => Err(PangError::DataNonExistent(loc)),
+    }
+}
+
+/// Gets the value at a dotted path.
+pub fn get_path(map: &LinkedHashMap<String, Data>, path: &str, loc: usize) -> Result<Data, PangError> {
+    if let Some(d) = map.get(path) {
+        return Ok(d.clone())
+    }
+    let (field, rest) = path.split_once('.').ok_or(PangError::DataNonExistent(loc))?;
+    match map.get(field) {
+        Some(d) if d.data_type == DataType::OBJECT => get_path(d.as_object().unwrap(), rest, loc),
+        _ => Err(PangError::DataNonExistent(loc)),
     }
 }
 
 pub struct TemplateBuilder {
     name: Option<String>,
-    data: Option<LinkedHashMap<String, Data>>
+    parent: Option<String>,
+    data: Option<LinkedHashMap<String, Data>>,
+    options: Option<LinkedHashMap<String, FieldOptions>>,
 }
 
 impl TemplateBuilder {
 
     pub fn build(self) -> Template {
         let data = self.data.unwrap_or_default();
+        let options = self.options.unwrap_or_default();
     
         Template {
             name: self.name.unwrap(),
+            version: first_version(),
+            parent: self.parent,
             data,
+            options,
+        }
+    }
+
+    /// Inherits every field, default and option of the parent [`Template`].
+    pub fn extend(&mut self, parent: &Template) -> Self {
+        self.parent = Some(parent.name.clone());
+        self.data = Some(parent.data.clone());
+        self.options = Some(parent.options.clone());
+        Self {
+            name: self.name.clone(),
+            parent: self.parent.clone(),
+            data: self.data.clone(),
+            options: self.options.clone(),
         }
     }
 
@@ -76,7 +461,24 @@ impl TemplateBuilder {
         self.data = Some(map);
         Self {
             name: self.name.clone(),
+            parent: self.parent.clone(),
+            data: self.data.clone(),
+            options: self.options.clone(),
+        }
+    }
+
+    /// Adds [`FieldOptions`] to a field, default options are not stored.
+    pub fn add_options(&mut self, name: String, options: FieldOptions) -> Self {
+        let mut map = self.options.clone().unwrap_or_default();
+        if options != FieldOptions::default() {
+            map.insert(name, options);
+        }
+        self.options = Some(map);
+        Self {
+            name: self.name.clone(),
+            parent: self.parent.clone(),
             data: self.data.clone(),
+            options: self.options.clone(),
         }
     }
 }
\ No newline at end of file
@@ -9,6 +9,22 @@ pub enum PangError {
     TemplateNonExistent(String, usize),
     TypeMismatch(usize),
     DataNonExistent(usize),
+    NestingCycle(String, usize),
+    TemplateReferenced(String, String, usize),
+    InstanceReferenced(String, String, usize),
+    ValueNotAllowed(Vec<String>, usize),
+    ConstraintViolation(String, String, usize),
+    UniqueViolation(String, String, usize),
+    DataAlreadyExists(String, usize),
+    ConversionFailed(String, String, usize),
+    VersionNonExistent(String, usize, usize),
+    ComputedField(String, usize),
+    DependencyCycle(String, usize),
+    ArithmeticError(usize),
+    DivisionByZero(usize),
+    UnconfirmedDelete(String, usize),
+    PreconditionFailed(String, usize),
+    ParameterMissing(String, usize),
     ExecutionError,
 }
 
@@ -29,6 +45,36 @@ impl PangError {
                 format!("Template {} doesn't exist.", name).to_string(),
             PangError::TypeMismatch(_) => "Unexpected data type.".to_string(),
             PangError::DataNonExistent(_) => "Data point doesn't exist in Template/Instance.".to_string(),
+            PangError::NestingCycle(name, _) => 
+                format!("Template {} can't be nested inside of itself.", name).to_string(),
+            PangError::TemplateReferenced(name, by, _) => 
+                format!("Template {} is still referenced by {}.", name, by).to_string(),
+            PangError::InstanceReferenced(name, by, _) => 
+                format!("Instance {} is still referenced by {}.", name, by).to_string(),
+            PangError::ValueNotAllowed(allowed, _) => 
+                format!("Value has to be one of: {}.", allowed.join(", ")).to_string(),
+            PangError::ConstraintViolation(name, constraint, _) => 
+                format!("Field {} violates constraint {}.", name, constraint).to_string(),
+            PangError::UniqueViolation(name, holder, _) => 
+                format!("Field {} has to be unique, the value is already used by {}.", name, holder).to_string(),
+            PangError::DataAlreadyExists(name, _) => 
+                format!("Data point {} already exists in Template.", name).to_string(),
+            PangError::ConversionFailed(name, owner, _) => 
+                format!("Field {} of {} can't be converted.", name, owner).to_string(),
+            PangError::VersionNonExistent(name, version, _) => 
+                format!("Template {} has no version {}.", name, version).to_string(),
+            PangError::ComputedField(name, _) => 
+                format!("Field {} is computed and can't be set.", name).to_string(),
+            PangError::DependencyCycle(name, _) => 
+                format!("Field {} depends on itself.", name).to_string(),
+            PangError::ArithmeticError(_) => "Arithmetic operation overflowed.".to_string(),
+            PangError::DivisionByZero(_) => "Division by zero.".to_string(),
+            PangError::UnconfirmedDelete(name, _) => 
+                format!("Deleting every instance of {} without a condition has to be confirmed.", name).to_string(),
+            PangError::PreconditionFailed(name, _) => 
+                format!("Instance {} was changed in between.", name).to_string(),
+            PangError::ParameterMissing(name, _) => 
+                format!("Parameter {} has no value.", name).to_string(),
         }
     }
 
@@ -43,9 +89,30 @@ impl PangError {
             PangError::TemplateNonExistent(_, loc) => *loc,
             PangError::TypeMismatch(loc) => *loc,
             PangError::DataNonExistent(loc) => *loc,
+            PangError::NestingCycle(_, loc) => *loc,
+            PangError::TemplateReferenced(_, _, loc) => *loc,
+            PangError::InstanceReferenced(_, _, loc) => *loc,
+            PangError::ValueNotAllowed(_, loc) => *loc,
+            PangError::ConstraintViolation(_, _, loc) => *loc,
+            PangError::UniqueViolation(_, _, loc) => *loc,
+            PangError::DataAlreadyExists(_, loc) => *loc,
+            PangError::ConversionFailed(_, _, loc) => *loc,
+            PangError::VersionNonExistent(_, _, loc) => *loc,
+            PangError::ComputedField(_, loc) => *loc,
+            PangError::DependencyCycle(_, loc) => *loc,
+            PangError::ArithmeticError(loc) => *loc,
+            PangError::DivisionByZero(loc) => *loc,
+            PangError::UnconfirmedDelete(_, loc) => *loc,
+            PangError::PreconditionFailed(_, loc) => *loc,
+            PangError::ParameterMissing(_, loc) => *loc,
         }
     }
 
+    /// Numeric code of the error.
+    ///
+    /// The lowest two bits number the errors of a subject, the next two name the subject:
+    /// `00` data, `01` instances, `10` templates and `11` the request itself.
+    /// Once the four codes of a subject are used, the bits above count up for the next four.
     pub fn code(&self) -> usize {
         match self {
             PangError::ExecutionError => 0b0000,
@@ -56,6 +123,22 @@ impl PangError {
             PangError::TemplateNonExistent(_, _) => 0b1010,
             PangError::TypeMismatch(_) => 0b0001,
             PangError::DataNonExistent(_) => 0b0010,
+            PangError::NestingCycle(_, _) => 0b1011,
+            PangError::TemplateReferenced(_, _, _) => 0b1000,
+            PangError::InstanceReferenced(_, _, _) => 0b0111,
+            PangError::ValueNotAllowed(_, _) => 0b0011,
+            PangError::ConstraintViolation(_, _, _) => 0b10001,
+            PangError::UniqueViolation(_, _, _) => 0b10100,
+            PangError::DataAlreadyExists(_, _) => 0b10000,
+            PangError::ConversionFailed(_, _, _) => 0b10010,
+            PangError::VersionNonExistent(_, _, _) => 0b11000,
+            PangError::ComputedField(_, _) => 0b10011,
+            PangError::DependencyCycle(_, _) => 0b100000,
+            PangError::ArithmeticError(_) => 0b100001,
+            PangError::DivisionByZero(_) => 0b100010,
+            PangError::UnconfirmedDelete(_, _) => 0b10101,
+            PangError::PreconditionFailed(_, _) => 0b10110,
+            PangError::ParameterMissing(_, _) => 0b11100,
         }
     }
 
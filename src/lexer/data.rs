@@ -5,7 +5,7 @@ use serde::{Serialize, Deserialize};
 
 /// The different Keywords used in PANG
 /// 
-/// It has 19 Tokens in total
+/// It has 91 Tokens in total
 #[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Token {
     Delete,
@@ -21,10 +21,83 @@ pub enum Token {
     StringType,
     IntegerType,
     FloatType,
+    Type,
+    Get,
+    Ref,
+    Restrict,
+    Cascade,
+    Null,
+    Expand,
+    Enum,
+    MaxLen,
+    Min,
+    Max,
+    Matches,
+    Not,
+    Empty,
+    Unique,
+    Alter,
+    Add,
+    Drop,
+    Rename,
+    Change,
+    Dry,
+    Run,
+    Version,
+    History,
+    Extends,
+    With,
+    Children,
+    Describe,
+    Starting,
+    Default,
+    Upsert,
+    Exists,
+    Update,
+    Where,
+    Count,
+    Confirm,
+    Mul,
+    Append,
+    Revision,
+    Like,
+    Starts,
+    Contains,
+    Ignore,
+    Case,
+    Name,
+    Searchable,
+    Search,
+    For,
+    Index,
+    Explain,
+    Order,
+    Asc,
+    Desc,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Equal,
+    NotEqual,
+    Less,
+    Greater,
+    LessEqual,
+    GreaterEqual,
+    Of,
+    On,
+    To,
+    As,
+    If,
+    And,
+    Or,
+    By,
     ENDL,
     Literal,
+    List,
     Integer,
     Float,
+    Parameter,
 }
 
 /// Defines where a given [`TokenDefinition`] has matched the source
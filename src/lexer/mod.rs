@@ -1,5 +1,8 @@
 use lazy_static::lazy_static;
 use data::{Token, TokenMatch, TokenDefinition};
+use serde_json::Value;
+
+use crate::error::PangError;
 
 pub mod data;
 pub mod tokens;
@@ -19,6 +22,7 @@ pub fn parse(source: String) -> Vec<Vec<TokenMatch>> {
             definition.match_text(&source, &mut token_matches)
         );
     token_matches.sort_by(|a, b| a.start.cmp(&b.start));
+    let token_matches = signed(token_matches);
     let mut lines = Vec::new();
     token_matches.split(|m| m.token == Token::ENDL)
     .collect::<Vec<&[TokenMatch]>>().iter()
@@ -27,11 +31,8 @@ pub fn parse(source: String) -> Vec<Vec<TokenMatch>> {
         for tm in *tms {
             let tm = match tm.token {
                 Token::Literal => {
-                    let value = tm.value.to_string()
-                        .strip_prefix("\"").unwrap().to_string()
-                        .strip_suffix("\"").unwrap().to_string();
                     let mut ctm = tm.clone();
-                    ctm.value = value;
+                    ctm.value = unescape(&tm.value[1..tm.value.len() - 1]);
                     ctm
                 },
                 _ => tm.clone(),
@@ -46,4 +47,131 @@ pub fn parse(source: String) -> Vec<Vec<TokenMatch>> {
     }
     lines.push(last);
     lines
-}
\ No newline at end of file
+}
+
+/// Joins a `-` with the number directly behind it into a negative number, if it doesn't follow an operand.
+///
+/// `MIN -5` and `"A" * -1` hold negative numbers, while `"A" -1` and `"A" - 1` stay subtractions.
+fn signed(token_matches: Vec<TokenMatch>) -> Vec<TokenMatch> {
+    let mut result: Vec<TokenMatch> = Vec::with_capacity(token_matches.len());
+    for tm in token_matches {
+        if let Some(minus) = result.last() {
+            let operand = result.len() > 1 && matches!(result[result.len() - 2].token,
+                Token::Literal | Token::Integer | Token::Float | Token::Parameter);
+            if minus.token == Token::Minus && minus.end == tm.start && !operand
+                && matches!(tm.token, Token::Integer | Token::Float) {
+                let minus = result.pop().unwrap();
+                result.push(TokenMatch { value: format!("-{}", tm.value), start: minus.start, ..tm });
+                continue
+            }
+        }
+        result.push(tm);
+    }
+    result
+}
+
+/// Decodes the escape sequences of a literal, unknown ones like the `\d` of a regex are kept as they are.
+pub fn unescape(literal: &str) -> String {
+    let mut value = String::with_capacity(literal.len());
+    let mut chars = literal.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            value.push(c);
+            continue
+        }
+        match chars.next() {
+            Some('"') => value.push('"'),
+            Some('\\') => value.push('\\'),
+            Some('n') => value.push('\n'),
+            Some('r') => value.push('\r'),
+            Some('t') => value.push('\t'),
+            Some(c) => {
+                value.push('\\');
+                value.push(c);
+            },
+            None => value.push('\\'),
+        }
+    }
+    value
+}
+
+/// Replaces every [`Token::Parameter`] with the value sent for it.
+/// 
+/// `$1` is bound to the first value of a list and `:name` to the value of a key,
+/// the values become tokens themselves, so they are never parsed as statements.
+pub fn bind(lines: Vec<Vec<TokenMatch>>, params: &Value) -> Result<Vec<Vec<TokenMatch>>, PangError> {
+    lines.into_iter().map(|line| line.into_iter().map(|tm| {
+        if tm.token != Token::Parameter {
+            return Ok(tm)
+        }
+        let value = match tm.value.strip_prefix('$') {
+            Some(position) => position.parse::<usize>().ok()
+                .and_then(|position| position.checked_sub(1))
+                .and_then(|index| params.get(index)),
+            None => params.get(&tm.value[1..]),
+        }.ok_or(PangError::ParameterMissing(tm.value.clone(), tm.start))?;
+        let (token, value) = match value {
+            Value::String(value) => (Token::Literal, value.clone()),
+            Value::Number(number) if number.is_i64() => (Token::Integer, number.to_string()),
+            Value::Number(number) if number.is_f64() => (Token::Float, number.to_string()),
+            _ => return Err(PangError::TypeMismatch(tm.start)),
+        };
+        Ok(TokenMatch { token, value, ..tm })
+    }).collect()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    /// Tokens and values of the bound lines
+    fn bound(source: &str, params: &Value) -> Result<Vec<Vec<(Token, String)>>, PangError> {
+        bind(parse(source.to_string()), params).map(|lines| lines.into_iter()
+            .map(|line| line.into_iter().map(|tm| (tm.token, tm.value)).collect())
+            .collect())
+    }
+
+    #[test]
+    fn literals_are_unescaped() {
+        let lines = parse(r#"CREATE "a \"quoted\" name" TEMPLATE "line\none\ttab \\ back \d";"#.to_string());
+        assert_eq!(lines[0][1].value, "a \"quoted\" name");
+        assert_eq!(lines[0][3].value, "line\none\ttab \\ back \\d");
+        assert_eq!(unescape(r"trailing\"), "trailing\\");
+    }
+
+    #[test]
+    fn negative_numbers() {
+        let lines = parse(r#"INTEGER "A" MIN -5 MAX -1; SET "B" VALUE "A" * -2.5; SET "C" VALUE "A" -1; SET "D" VALUE - 1;"#.to_string());
+        let tokens = |line: &Vec<TokenMatch>| line.iter().map(|tm| (tm.token, tm.value.clone())).collect::<Vec<_>>();
+        assert_eq!(tokens(&lines[0])[3..], [(Token::Integer, "-5".to_string()), (Token::Max, "MAX".to_string()), (Token::Integer, "-1".to_string())]);
+        assert_eq!(tokens(&lines[1])[5..], [(Token::Float, "-2.5".to_string())]);
+        assert_eq!(tokens(&lines[2])[4..], [(Token::Minus, "-".to_string()), (Token::Integer, "1".to_string())]);
+        assert_eq!(tokens(&lines[3])[3..], [(Token::Minus, "-".to_string()), (Token::Integer, "1".to_string())]);
+    }
+
+    #[test]
+    fn parameters_are_bound() {
+        let lines = bound("QUERY $1 FROM INSTANCE; SELECT $2; SET \"Hours\" VALUE $3; END $1;", &json!(["x\"; DELETE", -5, 2.5])).unwrap();
+        assert_eq!(lines[0][1], (Token::Literal, "x\"; DELETE".to_string()));
+        assert_eq!(lines[1][1], (Token::Integer, "-5".to_string()));
+        assert_eq!(lines[2][3], (Token::Float, "2.5".to_string()));
+        assert_eq!(lines[3][1], (Token::Literal, "x\"; DELETE".to_string()));
+
+        let lines = bound(r#"QUERY INSTANCE WHERE "Title" = :title;"#, &json!({ "title": "OR 1=1" })).unwrap();
+        assert_eq!(lines[0][5], (Token::Literal, "OR 1=1".to_string()));
+        // Placeholders inside of literals stay text
+        let lines = bound(r#"QUERY INSTANCE WHERE NAME = "a:b $1";"#, &Value::Null).unwrap();
+        assert_eq!(lines[0][5], (Token::Literal, "a:b $1".to_string()));
+    }
+
+    #[test]
+    fn missing_or_invalid_parameters() {
+        assert!(matches!(bound("QUERY $1 FROM INSTANCE;", &Value::Null), Err(PangError::ParameterMissing(name, 6)) if name == "$1"));
+        assert!(matches!(bound("QUERY $0 FROM INSTANCE;", &json!(["x"])), Err(PangError::ParameterMissing(name, _)) if name == "$0"));
+        assert!(matches!(bound("QUERY $2 FROM INSTANCE;", &json!(["x"])), Err(PangError::ParameterMissing(name, _)) if name == "$2"));
+        assert!(matches!(bound("QUERY :name FROM INSTANCE;", &json!({ "other": "x" })), Err(PangError::ParameterMissing(name, _)) if name == ":name"));
+        assert!(matches!(bound("QUERY $1 FROM INSTANCE;", &json!([true])), Err(PangError::TypeMismatch(6))));
+    }
+}
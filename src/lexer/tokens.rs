@@ -14,11 +14,90 @@ pub fn initialize() -> Vec<TokenDefinition> {
     token_definitions.push(TokenDefinition::new(Token::Template, r"TEMPLATE", 4));
     token_definitions.push(TokenDefinition::new(Token::Instance, r#"INSTANCE"#, 4));
     token_definitions.push(TokenDefinition::new(Token::Set, r"SET", 4));
+    // Before END, which is part of them
+    token_definitions.push(TokenDefinition::new(Token::Extends, r"EXTENDS", 4));
+    token_definitions.push(TokenDefinition::new(Token::Append, r"APPEND", 4));
     token_definitions.push(TokenDefinition::new(Token::End, r"END", 4));
     token_definitions.push(TokenDefinition::new(Token::StringType, r"STRING", 4));
     token_definitions.push(TokenDefinition::new(Token::IntegerType, r"INTEGER", 4));
     token_definitions.push(TokenDefinition::new(Token::FloatType, r"FLOAT", 4));
+    token_definitions.push(TokenDefinition::new(Token::Type, r"TYPE", 4));
+    token_definitions.push(TokenDefinition::new(Token::Get, r"GET", 4));
+    token_definitions.push(TokenDefinition::new(Token::Ref, r"REF", 4));
+    token_definitions.push(TokenDefinition::new(Token::Restrict, r"RESTRICT", 4));
+    token_definitions.push(TokenDefinition::new(Token::Cascade, r"CASCADE", 4));
+    token_definitions.push(TokenDefinition::new(Token::Null, r"NULL", 4));
+    token_definitions.push(TokenDefinition::new(Token::Expand, r"EXPAND", 4));
+    token_definitions.push(TokenDefinition::new(Token::Enum, r"ENUM", 4));
+    token_definitions.push(TokenDefinition::new(Token::MaxLen, r"MAXLEN", 4));
+    token_definitions.push(TokenDefinition::new(Token::Min, r"MIN", 4));
+    token_definitions.push(TokenDefinition::new(Token::Max, r"MAX", 4));
+    token_definitions.push(TokenDefinition::new(Token::Matches, r"MATCHES", 4));
+    token_definitions.push(TokenDefinition::new(Token::Not, r"NOT", 4));
+    token_definitions.push(TokenDefinition::new(Token::Empty, r"EMPTY", 4));
+    token_definitions.push(TokenDefinition::new(Token::Unique, r"UNIQUE", 4));
+    token_definitions.push(TokenDefinition::new(Token::Alter, r"ALTER", 4));
+    token_definitions.push(TokenDefinition::new(Token::Add, r"ADD", 4));
+    token_definitions.push(TokenDefinition::new(Token::Drop, r"DROP", 4));
+    token_definitions.push(TokenDefinition::new(Token::Rename, r"RENAME", 4));
+    token_definitions.push(TokenDefinition::new(Token::Change, r"CHANGE", 4));
+    token_definitions.push(TokenDefinition::new(Token::Dry, r"DRY", 4));
+    token_definitions.push(TokenDefinition::new(Token::Run, r"RUN", 4));
+    token_definitions.push(TokenDefinition::new(Token::Version, r"VERSION", 4));
+    token_definitions.push(TokenDefinition::new(Token::History, r"HISTORY", 4));
+    token_definitions.push(TokenDefinition::new(Token::With, r"WITH", 4));
+    token_definitions.push(TokenDefinition::new(Token::Children, r"CHILDREN", 4));
+    token_definitions.push(TokenDefinition::new(Token::Describe, r"DESCRIBE", 4));
+    token_definitions.push(TokenDefinition::new(Token::Starting, r"STARTING", 4));
+    token_definitions.push(TokenDefinition::new(Token::Default, r"DEFAULT", 4));
+    token_definitions.push(TokenDefinition::new(Token::Upsert, r"UPSERT", 4));
+    token_definitions.push(TokenDefinition::new(Token::Exists, r"EXISTS", 4));
+    token_definitions.push(TokenDefinition::new(Token::Update, r"UPDATE", 4));
+    token_definitions.push(TokenDefinition::new(Token::Where, r"WHERE", 4));
+    token_definitions.push(TokenDefinition::new(Token::Count, r"COUNT", 4));
+    token_definitions.push(TokenDefinition::new(Token::Confirm, r"CONFIRM", 4));
+    token_definitions.push(TokenDefinition::new(Token::Mul, r"MUL", 4));
+    token_definitions.push(TokenDefinition::new(Token::Revision, r"REVISION", 4));
+    token_definitions.push(TokenDefinition::new(Token::Like, r"LIKE", 4));
+    token_definitions.push(TokenDefinition::new(Token::Starts, r"STARTS", 4));
+    token_definitions.push(TokenDefinition::new(Token::Contains, r"CONTAINS", 4));
+    token_definitions.push(TokenDefinition::new(Token::Ignore, r"IGNORE", 4));
+    token_definitions.push(TokenDefinition::new(Token::Case, r"CASE", 4));
+    // After RENAME, which contains it
+    token_definitions.push(TokenDefinition::new(Token::Name, r"NAME", 4));
+    // Before SEARCH, which is part of it
+    token_definitions.push(TokenDefinition::new(Token::Searchable, r"SEARCHABLE", 4));
+    token_definitions.push(TokenDefinition::new(Token::Search, r"SEARCH", 4));
+    token_definitions.push(TokenDefinition::new(Token::For, r"FOR", 4));
+    token_definitions.push(TokenDefinition::new(Token::Index, r"INDEX", 4));
+    token_definitions.push(TokenDefinition::new(Token::Explain, r"EXPLAIN", 4));
+    token_definitions.push(TokenDefinition::new(Token::Order, r"ORDER", 4));
+    // After CASCADE and DESCRIBE, which contain them
+    token_definitions.push(TokenDefinition::new(Token::Asc, r"ASC", 4));
+    token_definitions.push(TokenDefinition::new(Token::Desc, r"DESC", 4));
+    token_definitions.push(TokenDefinition::new(Token::Plus, r"\+", 4));
+    token_definitions.push(TokenDefinition::new(Token::Minus, r"-", 4));
+    token_definitions.push(TokenDefinition::new(Token::Star, r"\*", 4));
+    token_definitions.push(TokenDefinition::new(Token::Slash, r"/", 4));
+    // Comparisons of two characters first
+    token_definitions.push(TokenDefinition::new(Token::NotEqual, r"!=", 4));
+    token_definitions.push(TokenDefinition::new(Token::LessEqual, r"<=", 4));
+    token_definitions.push(TokenDefinition::new(Token::GreaterEqual, r">=", 4));
+    token_definitions.push(TokenDefinition::new(Token::Equal, r"=", 4));
+    token_definitions.push(TokenDefinition::new(Token::Less, r"<", 4));
+    token_definitions.push(TokenDefinition::new(Token::Greater, r">", 4));
+    // Short keywords last, so they don't match inside of longer ones
+    token_definitions.push(TokenDefinition::new(Token::Of, r"OF", 4));
+    token_definitions.push(TokenDefinition::new(Token::On, r"ON", 4));
+    token_definitions.push(TokenDefinition::new(Token::To, r"TO", 4));
+    token_definitions.push(TokenDefinition::new(Token::As, r"AS", 4));
+    token_definitions.push(TokenDefinition::new(Token::If, r"IF", 4));
+    token_definitions.push(TokenDefinition::new(Token::And, r"AND", 4));
+    token_definitions.push(TokenDefinition::new(Token::Or, r"OR", 4));
+    token_definitions.push(TokenDefinition::new(Token::By, r"BY", 4));
+    token_definitions.push(TokenDefinition::new(Token::List, r#"\(\s*(?:"(?:[^"\\]|\\.)*"\s*,?\s*)*\)"#, 0));
     token_definitions.push(TokenDefinition::new(Token::Literal, r#""(?:[^"\\]|\\.)*""#, 1));
+    token_definitions.push(TokenDefinition::new(Token::Parameter, r#"\$\d+|:[A-Za-z_]\w*"#, 2));
     token_definitions.push(TokenDefinition::new(Token::Float, r#"\d+\.\d+"#, 2));
     token_definitions.push(TokenDefinition::new(Token::Integer, r#"\d+"#, 3));
 
@@ -1,4 +1,38 @@
-use crate::{data::{INSTANCES, structure::{Instance, Template}, TEMPLATES}, error::PangError};
+use linked_hash_map::LinkedHashMap;
+use serde_json::Value;
+use serde::Serialize;
+use std::{cmp::Ordering, collections::{BTreeMap, BTreeSet, HashMap}};
+
+use crate::{data::{INSTANCES, condition::Condition, expression::Expression, structure::{Instance, Template, OnDelete, FieldOptions, Description, object_prefix, get_path}, TEMPLATES, HISTORY, UNIQUE, UniqueIndex, SEARCH, search::SearchIndex, INDEXES, index::{OrderedIndex, Lookup}, serialization::{Data, DataType}}, error::PangError};
+
+/// How a statement finds the objects it works on
+#[derive(Serialize, Debug)]
+pub enum Access {
+    /// Looks up a single object by its name
+    Name(String),
+    /// Looks up fields in the indexes of a [`Template`]
+    Index { template: String, fields: Vec<String> },
+    /// Checks every instance of the templates, or every object if none is given
+    Scan(Vec<String>),
+}
+
+/// What a new [`Instance`] starts from
+pub enum Origin {
+    /// The starting values of a [`Template`]
+    Template(String),
+    /// The values of another [`Instance`] of the same template
+    Instance(String),
+}
+
+/// A change to the fields of an existing [`Template`]
+pub enum Alteration {
+    /// Adds a field with its default value and the options of the field and its nested fields.
+    Add(String, Data, Vec<(String, FieldOptions)>),
+    Drop(String),
+    Rename(String, String),
+    /// Converts a field and its values to another [`DataType`]
+    Change(String, DataType),
+}
 
 /// Pushes a [`Template`] to the static [`TEMPLATES`] mutex
 pub fn push_template(template: Template, loc: usize) -> Result<(), PangError> {
@@ -14,105 +48,855 @@ pub fn push_template(template: Template, loc: usize) -> Result<(), PangError> {
     Ok(())
 }
 
-/// Pushes a [`Instance`] to the static [`INSTANCES`] mutex
-pub fn push_instance(instance: Instance, loc: usize) -> Result<(), PangError> {
+/// Creates an [`Instance`] in the static [`INSTANCES`] mutex, `modify` sets its values.
+///
+/// The stores stay locked for the whole call, so the instance is only stored, if its values are valid
+/// against the current [`Template`], its references exist and its unique fields are free.
+pub fn create_instance(name: String, origin: Origin, loc: usize, modify: impl FnOnce(&mut Instance, &Template) -> Result<(), PangError>) -> Result<(), PangError> {
     let mut mutex = INSTANCES.lock().unwrap();
-    let res: Vec<&Instance> = mutex.iter().filter(|t| {
-        t.name == instance.name
-    }).collect();
-    if res.len() > 0 {
-        return Err(PangError::InstanceAlreadyExists(res[0].name.clone(), loc))
+    let templates = TEMPLATES.lock().unwrap();
+    if mutex.iter().any(|i| i.name == name) {
+        return Err(PangError::InstanceAlreadyExists(name, loc))
     }
+    let mut instance = match origin {
+        Origin::Template(template) => {
+            let template = templates.iter().find(|t| t.name == template).ok_or(PangError::TemplateNonExistent(template, loc))?;
+            let mut instance = Instance::new(name, template);
+            template.compute(&mut instance.data, loc)?;
+            instance
+        },
+        Origin::Instance(origin) => {
+            let origin = mutex.iter().find(|i| i.name == origin).ok_or(PangError::InstanceNonExistent(origin, loc))?;
+            let mut instance = Instance::new(name, template_of(&templates, origin, loc)?);
+            instance.data = origin.data.clone();
+            instance
+        },
+    };
+    let template = template_of(&templates, &instance, loc)?;
+    modify(&mut instance, template)?;
+    instance.validate(template, loc)?;
+    references_exist(&mutex, &templates, &instance, loc)?;
+    insert_instance(&mut mutex, &templates, instance, loc)
+}
 
+/// Adds a new [`Instance`] and its indexed fields, if its unique fields are free
+fn insert_instance(mutex: &mut Vec<Instance>, templates: &[Template], instance: Instance, loc: usize) -> Result<(), PangError> {
+    let mut unique = UNIQUE.lock().unwrap();
+    check_unique(&unique, templates, &instance, loc)?;
+    index_unique(&mut unique, templates, &instance);
+    SEARCH.lock().unwrap().index(templates, &instance);
+    INDEXES.lock().unwrap().index(templates, &instance);
     mutex.push(instance);
     Ok(())
 }
 
 /// Removes a [`Instance`] from the static [`INSTANCES`] mutex based on a name and returns the removed element
+/// 
+/// Instances referencing a removed element are handled based on their [`OnDelete`] option,
+/// if any of them restricts the deletion nothing is removed.
 pub fn remove_instance(name: String, loc: usize) -> Result<Instance, PangError> {
     let mut mutex = INSTANCES.lock().unwrap();
+    let templates = TEMPLATES.lock().unwrap();
+    let index = mutex.iter().position(|i| i.name == name).ok_or(PangError::InstanceNonExistent(name, loc))?;
+    let removed = mutex[index].clone();
+
+    let mut changes = Changes::default();
+    changes.removed.insert(index);
+    handle_references(&mutex, &templates, &mut changes, loc)?;
+    apply_changes(&mut mutex, &templates, &templates, changes, loc)?;
+    Ok(removed)
+}
+
+/// Removes every [`Instance`] of a [`Template`], that matches the condition, and returns them.
+/// 
+/// References are handled like on a single removal, nothing is removed if any of them restricts it.
+pub fn remove_instances(name: String, condition: Option<Condition>, loc: usize) -> Result<Vec<Instance>, PangError> {
+    let mut mutex = INSTANCES.lock().unwrap();
+    let templates = TEMPLATES.lock().unwrap();
+    let template = templates.iter().find(|t| t.name == name).ok_or(PangError::TemplateNonExistent(name.clone(), loc))?;
+    let candidates = condition.as_ref().and_then(|condition| INDEXES.lock().unwrap().candidates(template, condition));
+    let mut changes = Changes::default();
+    let mut removed = Vec::new();
+    for (index, instance) in mutex.iter().enumerate() {
+        let candidate = candidates.as_ref().is_none_or(|candidates| candidates.contains(&instance.name));
+        let matches = instance.template == name && candidate && match &condition {
+            Some(condition) => condition.matches(instance, loc)?,
+            None => true,
+        };
+        if matches {
+            changes.removed.insert(index);
+            removed.push(instance.clone());
+        }
+    }
+
+    handle_references(&mutex, &templates, &mut changes, loc)?;
+    apply_changes(&mut mutex, &templates, &templates, changes, loc)?;
+    Ok(removed)
+}
+
+/// Stored instances removed or replaced by a statement, by their position in the static [`INSTANCES`] mutex
+#[derive(Default)]
+struct Changes {
+    removed: BTreeSet<usize>,
+    replaced: BTreeMap<usize, Instance>,
+}
+
+/// Removes and replaces the changed instances and updates the indices of only those.
+///
+/// Instances are unindexed with the templates from `before` the change and indexed with those `after` it.
+/// A replaced instance gets a new revision, if it differs from the stored one.
+/// Nothing is changed if a replaced instance takes a unique value held by another one.
+fn apply_changes(mutex: &mut Vec<Instance>, before: &[Template], after: &[Template], mut changes: Changes, loc: usize) -> Result<(), PangError> {
+    for (index, instance) in changes.replaced.iter_mut() {
+        if instance != &mutex[*index] {
+            instance.revision += 1;
+        }
+    }
+    let previous: Vec<&Instance> = changes.removed.iter().chain(changes.replaced.keys()).map(|index| &mutex[*index]).collect();
+    let mut unique = UNIQUE.lock().unwrap();
+    for instance in previous.iter() {
+        unindex_unique(&mut unique, before, instance);
+    }
+    for (indexed, instance) in changes.replaced.values().enumerate() {
+        if let Err(e) = check_unique(&unique, after, instance, loc) {
+            for instance in changes.replaced.values().take(indexed) {
+                unindex_unique(&mut unique, after, instance);
+            }
+            for instance in previous.iter() {
+                index_unique(&mut unique, before, instance);
+            }
+            return Err(e)
+        }
+        index_unique(&mut unique, after, instance);
+    }
+    let mut search = SEARCH.lock().unwrap();
+    let mut indexes = INDEXES.lock().unwrap();
+    for instance in previous {
+        search.unindex(before, instance);
+        indexes.unindex(before, instance);
+    }
+    for instance in changes.replaced.values() {
+        search.index(after, instance);
+        indexes.index(after, instance);
+    }
+
+    for (index, instance) in changes.replaced {
+        mutex[index] = instance;
+    }
     let mut index = 0;
-    for (i, instance) in mutex.iter().enumerate() {
-        if instance.name == name {
-            index = i;
-            break;
+    mutex.retain(|_| {
+        index += 1;
+        !changes.removed.contains(&(index - 1))
+    });
+    Ok(())
+}
+
+/// Returns the fields of the instances that aren't removed, which reference the removed instance `target`
+fn referencing(instances: &[Instance], templates: &[Template], changes: &Changes, target: usize, loc: usize) -> Result<Vec<(usize, String, OnDelete)>, PangError> {
+    let target = &instances[target];
+    let mut fields = Vec::new();
+    for (index, stored) in instances.iter().enumerate() {
+        if changes.removed.contains(&index) {
+            continue
         }
-        if mutex.len()-1 == i {
-            return Err(PangError::InstanceNonExistent(name, loc))
+        let template = template_of(templates, stored, loc)?;
+        let instance = changes.replaced.get(&index).unwrap_or(stored);
+        for (field, options) in template.options.iter() {
+            let reference = match &options.reference {
+                Some(reference) if reference.template == target.template => reference,
+                _ => continue,
+            };
+            if instance.get(field, loc)?.as_str() == Some(target.name.as_str()) {
+                fields.push((index, field.clone(), reference.on_delete));
+            }
         }
     }
-    if mutex.len() > 1 {
-        Ok(mutex.swap_remove(index))
-    } else if mutex.len() != 0 {
-        Ok(mutex.remove(index))
-    } else {
-        Err(PangError::InstanceNonExistent(name, loc))
+    Ok(fields)
+}
+
+/// Handles the instances referencing removed ones based on their [`OnDelete`] option.
+///
+/// All cascades are removed first, so only instances that stay can restrict the removal or have their references cleared.
+fn handle_references(instances: &[Instance], templates: &[Template], changes: &mut Changes, loc: usize) -> Result<(), PangError> {
+    let mut queue: Vec<usize> = changes.removed.iter().copied().collect();
+    while let Some(target) = queue.pop() {
+        for (index, _, on_delete) in referencing(instances, templates, changes, target, loc)? {
+            if on_delete == OnDelete::CASCADE && changes.removed.insert(index) {
+                changes.replaced.remove(&index);
+                queue.push(index);
+            }
+        }
     }
+
+    let removed: Vec<usize> = changes.removed.iter().copied().collect();
+    for target in removed {
+        for (index, field, on_delete) in referencing(instances, templates, changes, target, loc)? {
+            match on_delete {
+                OnDelete::RESTRICT => return Err(PangError::InstanceReferenced(instances[target].name.clone(), instances[index].name.clone(), loc)),
+                OnDelete::CASCADE => unreachable!("cascades are removed before"),
+                OnDelete::NULL => {
+                    let template = template_of(templates, &instances[index], loc)?;
+                    let instance = changes.replaced.entry(index).or_insert_with(|| instances[index].clone());
+                    instance.clear(&field, loc)?;
+                    template.compute(&mut instance.data, loc)?;
+                },
+            }
+        }
+    }
+    Ok(())
 }
 
-/// Removes a [`Template`] from the static [`TEMPLATES`] mutex based on a name and returns the removed element
-pub fn remove_template(name: String, loc: usize) -> Result<Template, PangError> {
+/// Modifies the [`Instance`] with the given name under the lock of the static [`INSTANCES`] mutex.
+/// 
+/// The modification works on a copy, that only replaces the stored instance if its references and unique fields are valid.
+pub fn modify_instance<R>(name: String, loc: usize, modify: impl FnOnce(&mut Instance, &Template) -> Result<R, PangError>) -> Result<R, PangError> {
+    let mut mutex = INSTANCES.lock().unwrap();
+    let templates = TEMPLATES.lock().unwrap();
+    let index = mutex.iter().position(|i| i.name == name).ok_or(PangError::InstanceNonExistent(name, loc))?;
+    let template = template_of(&templates, &mutex[index], loc)?;
+    let mut instance = mutex[index].clone();
+    let res = modify(&mut instance, template)?;
+    references_exist(&mutex, &templates, &instance, loc)?;
+    apply_changes(&mut mutex, &templates, &templates, Changes { replaced: BTreeMap::from([(index, instance)]), ..Default::default() }, loc)?;
+    Ok(res)
+}
+
+/// Modifies the [`Instance`] with the given name or creates it from a [`Template`], if it doesn't exist,
+/// under a single lock of the static [`INSTANCES`] mutex. Returns whether the instance was created.
+pub fn upsert_instance(name: String, template: String, loc: usize, modify: impl FnOnce(&mut Instance, &Template) -> Result<(), PangError>) -> Result<bool, PangError> {
+    let mut mutex = INSTANCES.lock().unwrap();
+    let templates = TEMPLATES.lock().unwrap();
+    match mutex.iter().position(|i| i.name == name) {
+        Some(index) => {
+            if mutex[index].template != template {
+                return Err(PangError::TypeMismatch(loc))
+            }
+            let mut instance = mutex[index].clone();
+            modify(&mut instance, template_of(&templates, &mutex[index], loc)?)?;
+            references_exist(&mutex, &templates, &instance, loc)?;
+            apply_changes(&mut mutex, &templates, &templates, Changes { replaced: BTreeMap::from([(index, instance)]), ..Default::default() }, loc)?;
+            Ok(false)
+        },
+        None => {
+            let template = templates.iter().find(|t| t.name == template).ok_or(PangError::TemplateNonExistent(template, loc))?;
+            let mut instance = Instance::new(name, template);
+            template.compute(&mut instance.data, loc)?;
+            modify(&mut instance, template)?;
+            instance.validate(template, loc)?;
+            references_exist(&mutex, &templates, &instance, loc)?;
+            insert_instance(&mut mutex, &templates, instance, loc)?;
+            Ok(true)
+        },
+    }
+}
+
+/// Values of the unique fields of an [`Instance`] and their key in the [`UniqueIndex`]
+fn unique_values(templates: &[Template], instance: &Instance) -> Vec<((String, String), String)> {
+    let template = match template_of(templates, instance, 0) {
+        Ok(template) => template,
+        Err(_) => return Vec::new(),
+    };
+    template.options.iter()
+        .filter(|(_, options)| options.unique)
+        .filter_map(|(field, _)| {
+            let data = instance.get(field, 0).ok()?;
+            if data.as_str() == Some("") {
+                return None
+            }
+            Some(((instance.template.clone(), field.clone()), serde_json::to_string(&data).ok()?))
+        }).collect()
+}
+
+/// Checks that no other [`Instance`] holds the value of a unique field
+fn check_unique(unique: &UniqueIndex, templates: &[Template], instance: &Instance, loc: usize) -> Result<(), PangError> {
+    for (key, value) in unique_values(templates, instance) {
+        if let Some(holder) = unique.get(&key).and_then(|values| values.get(&value)) {
+            if holder != &instance.name {
+                return Err(PangError::UniqueViolation(key.1, holder.clone(), loc))
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Adds the unique fields of an [`Instance`] to the [`UniqueIndex`]
+fn index_unique(unique: &mut UniqueIndex, templates: &[Template], instance: &Instance) {
+    for (key, value) in unique_values(templates, instance) {
+        unique.entry(key).or_default().insert(value, instance.name.clone());
+    }
+}
+
+/// Removes the unique fields of an [`Instance`] from the [`UniqueIndex`]
+fn unindex_unique(unique: &mut UniqueIndex, templates: &[Template], instance: &Instance) {
+    for (key, value) in unique_values(templates, instance) {
+        if let Some(values) = unique.get_mut(&key) {
+            values.remove(&value);
+        }
+    }
+}
+
+/// Rebuilds the static [`UNIQUE`], [`SEARCH`] and [`INDEXES`] indices from the static [`INSTANCES`] mutex
+pub fn rebuild_indices() {
+    let mutex = INSTANCES.lock().unwrap();
+    let templates = TEMPLATES.lock().unwrap();
+    let mut unique = UniqueIndex::new();
+    for instance in mutex.iter() {
+        index_unique(&mut unique, &templates, instance);
+    }
+    *UNIQUE.lock().unwrap() = unique;
+    *SEARCH.lock().unwrap() = SearchIndex::build(&mutex, &templates);
+    *INDEXES.lock().unwrap() = OrderedIndex::build(&mutex, &templates);
+}
+
+/// Overwrites fields of every [`Instance`] of a [`Template`], that matches the condition.
+/// 
+/// Nothing is changed if any of the instances fails, returns the number of updated instances.
+pub fn update_instances(name: String, sets: Vec<(String, Expression, usize)>, condition: Option<Condition>, loc: usize) -> Result<usize, PangError> {
+    let mut mutex = INSTANCES.lock().unwrap();
+    let templates = TEMPLATES.lock().unwrap();
+    let template = templates.iter().find(|t| t.name == name).ok_or(PangError::TemplateNonExistent(name.clone(), loc))?;
+    let candidates = condition.as_ref().and_then(|condition| INDEXES.lock().unwrap().candidates(template, condition));
+    let mut changes = Changes::default();
+    for (index, stored) in mutex.iter().enumerate().filter(|(_, i)| i.template == name) {
+        if candidates.as_ref().is_some_and(|candidates| !candidates.contains(&stored.name)) {
+            continue
+        }
+        if let Some(condition) = &condition {
+            if !condition.matches(stored, loc)? {
+                continue
+            }
+        }
+        let mut instance = stored.clone();
+        for (field, expression, loc) in sets.iter() {
+            instance.assign(template, field.clone(), expression, *loc)?;
+            template.compute(&mut instance.data, *loc)?;
+        }
+        references_exist(&mutex, &templates, &instance, loc)?;
+        changes.replaced.insert(index, instance);
+    }
+    let count = changes.replaced.len();
+    apply_changes(&mut mutex, &templates, &templates, changes, loc)?;
+    Ok(count)
+}
+
+/// Alters the fields of a [`Template`] and migrates every [`Instance`] of it.
+/// 
+/// Templates extending or embedding the altered one and their instances are migrated as well,
+/// nothing is changed if any of the alterations fails.
+/// A dry run changes nothing and returns every [`Instance`], that couldn't be migrated.
+pub fn alter_template(name: String, alterations: Vec<Alteration>, dry: bool, loc: usize) -> Result<Vec<(String, PangError)>, PangError> {
+    let mut inst_mutex = INSTANCES.lock().unwrap();
     let mut temp_mutex = TEMPLATES.lock().unwrap();
-    let mut index = 0;
-    for (i, template) in temp_mutex.iter().enumerate() {
-        if template.name == name {
-            index = i;
-            break;
+    let mut templates = temp_mutex.clone();
+
+    let options = match templates.iter().find(|t| t.name == name) {
+        Some(template) => template.options.clone(),
+        None => return Err(PangError::TemplateNonExistent(name, loc)),
+    };
+    // References and nested templates keep their type, starting values are converted as well
+    for alteration in alterations.iter() {
+        if let Alteration::Change(field, data_type) = alteration {
+            if options.get(field).is_some_and(|o| o.reference.is_some() || o.template.is_some()) {
+                return Err(PangError::TypeMismatch(loc))
+            }
+            if let Some(starting) = options.get(field).and_then(|o| o.starting.as_ref()) {
+                starting.convert(*data_type).ok_or_else(|| PangError::ConversionFailed(field.clone(), name.clone(), loc))?;
+            }
+        }
+    }
+
+    // Every template and the path, where the altered fields are found.
+    // Templates extending an altered one are altered as a whole, so their children and embedding templates are as well.
+    let mut targets: Vec<(String, String)> = vec![(name.clone(), String::new())];
+    let mut i = 0;
+    while i < targets.len() {
+        let (target, prefix) = targets[i].clone();
+        i += 1;
+        if !prefix.is_empty() {
+            continue
         }
-        if temp_mutex.len()-1 == i {
-            return Err(PangError::TemplateNonExistent(name, loc))
+        for template in templates.iter() {
+            if template.parent.as_ref() == Some(&target) {
+                targets.push((template.name.clone(), String::new()));
+            }
+            for (field, options) in template.options.iter() {
+                if options.template.as_ref() == Some(&target) {
+                    targets.push((template.name.clone(), format!("{}.", field)));
+                }
+            }
         }
     }
-    // Remove template
-    let temp = {
-        if temp_mutex.len() > 1 {
-            Ok(temp_mutex.swap_remove(index))
-        } else if temp_mutex.len() != 0 {
-            Ok(temp_mutex.remove(index))
-        } else {
-            Err(PangError::InstanceNonExistent(name, loc))
+
+    // Keep the previous definitions and count up the versions
+    let mut history = Vec::new();
+    for template in templates.iter_mut().filter(|t| targets.iter().any(|(target, _)| target == &t.name)) {
+        history.push(template.clone());
+        template.version += 1;
+    }
+
+    // Instances, that can't be migrated
+    let mut failures: Vec<(String, PangError)> = Vec::new();
+    let mut changes = Changes::default();
+    for (target, prefix) in targets {
+        let template = templates.iter_mut().find(|t| t.name == target).unwrap();
+        alter_object(&mut template.data, &prefix, &alterations, &target, loc)?;
+        template.options = alter_options(&template.options, &prefix, &alterations);
+        let mut data = template.data.clone();
+        template.compute(&mut data, loc)?;
+        template.data = data;
+        let template = template.clone();
+        for (index, stored) in inst_mutex.iter().enumerate().filter(|(_, i)| i.template == target) {
+            if failures.iter().any(|(name, _)| name == &stored.name) {
+                continue
+            }
+            // A template embedding the altered one more than once is migrated once per field
+            let mut instance = changes.replaced.get(&index).unwrap_or(stored).clone();
+            let res = alter_object(&mut instance.data, &prefix, &alterations, &instance.name, loc)
+                .and_then(|_| start_added(&mut instance, &template, &prefix, &alterations, loc))
+                .and_then(|_| template.compute(&mut instance.data, loc));
+            let res = match res {
+                Ok(()) => instance.validate(&template, loc).map(|_| instance.version = template.version),
+                Err(e) => Err(e),
+            };
+            match res {
+                Ok(()) => changes.replaced.insert(index, instance),
+                Err(e) => {
+                    failures.push((instance.name.clone(), e));
+                    changes.replaced.remove(&index)
+                },
+            };
         }
-    }?;
+    }
+
+    // Unique values are kept per template, so only the migrated instances can conflict with each other
+    let mut unique = UniqueIndex::new();
+    for instance in changes.replaced.values() {
+        match check_unique(&unique, &templates, instance, loc) {
+            Ok(()) => index_unique(&mut unique, &templates, instance),
+            Err(e) => failures.push((instance.name.clone(), e)),
+        }
+    }
 
-    // Remove instances
+    if dry {
+        return Ok(failures)
+    }
+    if let Some((_, e)) = failures.into_iter().next() {
+        return Err(e)
+    }
+    apply_changes(&mut inst_mutex, &temp_mutex, &templates, changes, loc)?;
+    HISTORY.lock().unwrap().append(&mut history);
+    *temp_mutex = templates;
+    Ok(Vec::new())
+}
+
+/// Sets the fields added to an [`Instance`] to their starting value, like a new instance starts with
+fn start_added(instance: &mut Instance, template: &Template, prefix: &str, alterations: &[Alteration], loc: usize) -> Result<(), PangError> {
+    for alteration in alterations {
+        if let Alteration::Add(field, _, _) = alteration {
+            let path = format!("{}{}", prefix, field);
+            if template.options.get(&path).is_some_and(|options| options.computed.is_some()) {
+                continue
+            }
+            let starting = template.starting(&path, loc)?;
+            instance.overwrite(template, path, starting, loc)?;
+        }
+    }
+    Ok(())
+}
+
+/// Applies alterations to the fields of the object at a dotted path prefix
+fn alter_object(data: &mut LinkedHashMap<String, Data>, prefix: &str, alterations: &Vec<Alteration>, owner: &str, loc: usize) -> Result<(), PangError> {
+    let path = match prefix.strip_suffix('.') {
+        Some(path) => path,
+        None => return alter_fields(data, alterations, owner, loc),
+    };
+    let (field, rest) = path.split_once('.').unwrap_or((path, ""));
+    match data.get_mut(field) {
+        Some(d) if d.data_type == DataType::OBJECT => {
+            let mut object = d.as_object().cloned().unwrap_or_default();
+            let rest = if rest.is_empty() { String::new() } else { format!("{}.", rest) };
+            alter_object(&mut object, &rest, alterations, owner, loc)?;
+            *d = object.into();
+            Ok(())
+        },
+        _ => Err(PangError::DataNonExistent(loc)),
+    }
+}
+
+/// Applies alterations to the fields of an object, that belongs to the named template or instance
+fn alter_fields(data: &mut LinkedHashMap<String, Data>, alterations: &Vec<Alteration>, owner: &str, loc: usize) -> Result<(), PangError> {
+    for alteration in alterations {
+        match alteration {
+            Alteration::Add(field, default, _) => {
+                if data.contains_key(field) {
+                    return Err(PangError::DataAlreadyExists(field.clone(), loc))
+                }
+                data.insert(field.clone(), default.clone());
+            },
+            Alteration::Drop(field) => {
+                if data.remove(field).is_none() {
+                    return Err(PangError::DataNonExistent(loc))
+                }
+            },
+            Alteration::Rename(from, to) => {
+                if data.contains_key(to) {
+                    return Err(PangError::DataAlreadyExists(to.clone(), loc))
+                }
+                if !data.contains_key(from) {
+                    return Err(PangError::DataNonExistent(loc))
+                }
+                // Rebuild to keep the order of the fields
+                *data = data.iter()
+                    .map(|(k, v)| (if k == from { to.clone() } else { k.clone() }, v.clone()))
+                    .collect();
+            },
+            Alteration::Change(field, data_type) => {
+                let value = data.get_mut(field).ok_or(PangError::DataNonExistent(loc))?;
+                if value.data_type == DataType::OBJECT {
+                    return Err(PangError::TypeMismatch(loc))
+                }
+                *value = value.convert(*data_type)
+                    .ok_or_else(|| PangError::ConversionFailed(field.clone(), owner.to_string(), loc))?;
+            },
+        }
+    }
+    Ok(())
+}
+
+/// Applies alterations to the options of the fields below a dotted path prefix
+fn alter_options(options: &LinkedHashMap<String, FieldOptions>, prefix: &str, alterations: &Vec<Alteration>) -> LinkedHashMap<String, FieldOptions> {
+    let mut options = options.clone();
+    // Whether a key is the field or one of its nested fields
+    let belongs = |key: &String, field: &String| {
+        let field = format!("{}{}", prefix, field);
+        key == &field || key.starts_with(&format!("{}.", field))
+    };
+    for alteration in alterations {
+        options = match alteration {
+            Alteration::Add(_, _, added) => {
+                for (key, field_options) in added {
+                    if field_options != &FieldOptions::default() {
+                        options.insert(format!("{}{}", prefix, key), field_options.clone());
+                    }
+                }
+                options
+            },
+            Alteration::Drop(field) => options.into_iter()
+                .filter(|(key, _)| !belongs(key, field))
+                .collect(),
+            Alteration::Rename(from, to) => options.into_iter()
+                .map(|(key, mut field_options)| {
+                    // Computed fields next to the renamed one depend on it by its relative name
+                    if let Some(expression) = field_options.computed.as_mut() {
+                        if object_prefix(&key) == prefix {
+                            expression.rename(from, to);
+                        }
+                    }
+                    match belongs(&key, from) {
+                        true => (format!("{}{}{}", prefix, to, &key[prefix.len() + from.len()..]), field_options),
+                        false => (key, field_options),
+                    }
+                }).collect(),
+            Alteration::Change(field, data_type) => {
+                if let Some(field_options) = options.get_mut(&format!("{}{}", prefix, field)) {
+                    field_options.starting = field_options.starting.as_ref().and_then(|s| s.convert(*data_type));
+                    // Drop the constraints, that don't apply to the new type
+                    match data_type {
+                        DataType::STRING => {
+                            field_options.min = None;
+                            field_options.max = None;
+                        },
+                        _ => {
+                            field_options.allowed = None;
+                            field_options.max_len = None;
+                            field_options.matches = None;
+                            field_options.not_empty = false;
+                            field_options.searchable = false;
+                        },
+                    }
+                }
+                options.into_iter()
+                    .filter(|(_, field_options)| field_options != &FieldOptions::default())
+                    .collect()
+            },
+        };
+    }
+    options
+}
+
+/// Checks that every reference of an [`Instance`] is empty or names one of the given instances of the referenced [`Template`]
+fn references_exist(mutex: &[Instance], templates: &[Template], instance: &Instance, loc: usize) -> Result<(), PangError> {
+    for (field, options) in template_of(templates, instance, loc)?.options.iter() {
+        let reference = match &options.reference {
+            Some(reference) => reference,
+            None => continue,
+        };
+        let name = instance.get(field, loc)?.as_str().unwrap_or_default().to_string();
+        if name.is_empty() {
+            continue
+        }
+        // The instance itself isn't part of the store during a selection
+        let template = match mutex.iter().find(|i| i.name == name) {
+            _ if name == instance.name => &instance.template,
+            Some(referenced) => &referenced.template,
+            None => return Err(PangError::InstanceNonExistent(name.to_string(), loc)),
+        };
+        if template != &reference.template {
+            return Err(PangError::TypeMismatch(loc))
+        }
+    }
+    Ok(())
+}
+
+/// Serializes the instances with every reference replaced by the referenced [`Instance`]
+pub fn expand_references(instances: Vec<Instance>) -> Vec<Value> {
+    let mutex = INSTANCES.lock().unwrap();
+    let templates = TEMPLATES.lock().unwrap();
+    instances.into_iter().map(|instance| {
+        let mut value = serde_json::to_value(&instance).unwrap_or_default();
+        let options = match template_of(&templates, &instance, 0) {
+            Ok(template) => template.options.clone(),
+            Err(_) => LinkedHashMap::new(),
+        };
+        for (field, options) in options.iter() {
+            if options.reference.is_none() {
+                continue
+            }
+            let name = match instance.get(field, 0) {
+                Ok(data) => data.as_str().unwrap_or_default().to_string(),
+                Err(_) => continue,
+            };
+            let referenced = match mutex.iter().find(|i| i.name == name) {
+                Some(referenced) => referenced,
+                None => continue,
+            };
+            let slot = field.split('.').try_fold(&mut value["data"], |v, key| v.get_mut(key));
+            if let Some(slot) = slot {
+                *slot = serde_json::to_value(referenced).unwrap_or_default();
+            }
+        }
+        value
+    }).collect()
+}
+
+/// Removes a [`Template`] from the static [`TEMPLATES`] mutex based on a name and returns the removed element
+pub fn remove_template(name: String, loc: usize) -> Result<Template, PangError> {
     let mut inst_mutex = INSTANCES.lock().unwrap();
-    inst_mutex.drain_filter(|inst| {
-        inst.template == temp
-    });
+    let mut temp_mutex = TEMPLATES.lock().unwrap();
+    let index = match temp_mutex.iter().position(|t| t.name == name) {
+        Some(index) => index,
+        None => return Err(PangError::TemplateNonExistent(name, loc)),
+    };
+    // Fields of other templates can't be left referencing or embedding it
+    for template in temp_mutex.iter().filter(|t| t.name != name) {
+        let referencing = template.options.values().any(|options| {
+            options.reference.as_ref().is_some_and(|reference| reference.template == name) || options.template.as_ref() == Some(&name)
+        });
+        if referencing {
+            return Err(PangError::TemplateReferenced(name, template.name.clone(), loc))
+        }
+    }
 
+    // Remove the instances of the template like single ones
+    let removed = inst_mutex.iter().enumerate().filter(|(_, inst)| inst.template == name).map(|(index, _)| index).collect();
+    let mut changes = Changes { removed, ..Default::default() };
+    handle_references(&inst_mutex, &temp_mutex, &mut changes, loc)?;
+    apply_changes(&mut inst_mutex, &temp_mutex, &temp_mutex, changes, loc)?;
+
+    let temp = temp_mutex.remove(index);
+    // Children keep the inherited fields and extend the grandparent instead
+    for template in temp_mutex.iter_mut().filter(|t| t.parent.as_ref() == Some(&name)) {
+        template.parent = temp.parent.clone();
+    }
+    HISTORY.lock().unwrap().retain(|t| t.name != name);
     Ok(temp)
 }
 
-/// Copies a [`Instance`] from the static [`INSTANCES`] mutex based on a name and returns it
-pub fn copy_instance(name: String, loc: usize) -> Result<Instance, PangError> {
+/// Searches the searchable fields of the instances of a [`Template`] and returns them ranked by relevance
+pub fn search_instances(name: String, query: String, loc: usize) -> Result<Vec<Instance>, PangError> {
     let mutex = INSTANCES.lock().unwrap();
-    let mut index = 0;
-    for (i, instance) in mutex.iter().enumerate() {
-        if instance.name == name {
-            index = i;
-            break;
+    let templates = TEMPLATES.lock().unwrap();
+    if !templates.iter().any(|t| t.name == name) {
+        return Err(PangError::TemplateNonExistent(name, loc))
+    }
+    let total = mutex.iter().filter(|i| i.template == name).count();
+    let ranked = SEARCH.lock().unwrap().search(&name, &query, total);
+    Ok(ranked.into_iter().filter_map(|(instance, _)| mutex.iter().find(|i| i.name == instance).cloned()).collect())
+}
+
+/// Estimates the number of instances a search of a [`Template`] returns without ranking them
+pub fn estimate_search(name: String, query: String, loc: usize) -> Result<usize, PangError> {
+    let mutex = INSTANCES.lock().unwrap();
+    let templates = TEMPLATES.lock().unwrap();
+    if !templates.iter().any(|t| t.name == name) {
+        return Err(PangError::TemplateNonExistent(name, loc))
+    }
+    let total = mutex.iter().filter(|i| i.template == name).count();
+    Ok(SEARCH.lock().unwrap().estimate(&name, &query).min(total))
+}
+
+/// Renames an [`Instance`] and updates every reference to it
+pub fn rename_instance(from: String, to: String, loc: usize) -> Result<Instance, PangError> {
+    let mut mutex = INSTANCES.lock().unwrap();
+    let templates = TEMPLATES.lock().unwrap();
+    if mutex.iter().any(|i| i.name == to) {
+        return Err(PangError::InstanceAlreadyExists(to, loc))
+    }
+    let index = mutex.iter().position(|i| i.name == from).ok_or_else(|| PangError::InstanceNonExistent(from.clone(), loc))?;
+    let mut renamed = mutex[index].clone();
+    renamed.name = to.clone();
+    let mut changes = Changes::default();
+    changes.replaced.insert(index, renamed);
+
+    for (position, stored) in mutex.iter().enumerate() {
+        let template = template_of(&templates, stored, loc)?;
+        let instance = changes.replaced.get(&position).unwrap_or(stored);
+        let mut fields = Vec::new();
+        for (field, options) in template.options.iter() {
+            match &options.reference {
+                Some(reference) if reference.template == mutex[index].template => {},
+                _ => continue,
+            };
+            if instance.get(field, loc)?.as_str() == Some(from.as_str()) {
+                fields.push(field);
+            }
+        }
+        if fields.is_empty() {
+            continue
         }
-        if mutex.len()-1 == i {
-            return Err(PangError::InstanceNonExistent(name, loc))
+        let instance = changes.replaced.entry(position).or_insert_with(|| stored.clone());
+        for field in fields {
+            instance.overwrite(template, field.clone(), to.clone().into(), loc)?;
         }
+        template.compute(&mut instance.data, loc)?;
+    }
+    apply_changes(&mut mutex, &templates, &templates, changes, loc)?;
+    Ok(mutex[index].clone())
+}
+
+/// Renames a [`Template`] with its previous versions and updates its instances and every template depending on it
+pub fn rename_template(from: String, to: String, loc: usize) -> Result<Template, PangError> {
+    let mut inst_mutex = INSTANCES.lock().unwrap();
+    let mut temp_mutex = TEMPLATES.lock().unwrap();
+    if temp_mutex.iter().any(|t| t.name == to) {
+        return Err(PangError::TemplateAlreadyExists(to, loc))
+    }
+    if !temp_mutex.iter().any(|t| t.name == from) {
+        return Err(PangError::TemplateNonExistent(from, loc))
+    }
+
+    let mut templates = temp_mutex.clone();
+    let mut history = HISTORY.lock().unwrap();
+    let mut previous = history.clone();
+    for template in templates.iter_mut().chain(previous.iter_mut()) {
+        rename_template_refs(template, &from, &to);
+    }
+    let mut changes = Changes::default();
+    for (index, stored) in inst_mutex.iter().enumerate().filter(|(_, i)| i.template == from) {
+        let mut instance = stored.clone();
+        instance.template = to.clone();
+        changes.replaced.insert(index, instance);
+    }
+    apply_changes(&mut inst_mutex, &temp_mutex, &templates, changes, loc)?;
+    let renamed = templates.iter().find(|t| t.name == to).unwrap().clone();
+    *history = previous;
+    *temp_mutex = templates;
+    Ok(renamed)
+}
+
+/// Replaces the name of a [`Template`] in another one, its parent and its nested and referenced templates
+fn rename_template_refs(template: &mut Template, from: &str, to: &str) {
+    if template.name == from {
+        template.name = to.to_string();
+    }
+    if template.parent.as_deref() == Some(from) {
+        template.parent = Some(to.to_string());
+    }
+    for (_, options) in template.options.iter_mut() {
+        if options.template.as_deref() == Some(from) {
+            options.template = Some(to.to_string());
+        }
+        if let Some(reference) = options.reference.as_mut().filter(|r| r.template == from) {
+            reference.template = to.to_string();
+        }
+    }
+}
+
+/// Counts the instances in the static [`INSTANCES`] mutex, optionally only those with a name
+pub fn count_instances(name: Option<&String>) -> usize {
+    INSTANCES.lock().unwrap().iter().filter(|i| name.is_none_or(|name| &i.name == name)).count()
+}
+
+/// Counts the templates in the static [`TEMPLATES`] mutex, optionally only those with a name
+pub fn count_templates(name: Option<&String>) -> usize {
+    TEMPLATES.lock().unwrap().iter().filter(|t| name.is_none_or(|name| &t.name == name)).count()
+}
+
+/// Counts the versions of a [`Template`], including the current one
+pub fn count_template_versions(name: &String) -> usize {
+    match count_templates(Some(name)) {
+        0 => 0,
+        _ => HISTORY.lock().unwrap().iter().filter(|t| &t.name == name).count() + 1,
+    }
+}
+
+/// Copies a [`Instance`] from the static [`INSTANCES`] mutex based on a name and returns it
+pub fn copy_instance(name: String, loc: usize) -> Result<Instance, PangError> {
+    let mutex = INSTANCES.lock().unwrap();
+    match mutex.iter().find(|i| i.name == name) {
+        Some(instance) => Ok(instance.clone()),
+        None => Err(PangError::InstanceNonExistent(name, loc)),
     }
-    Ok(mutex.get(index).unwrap().clone())
 }
 
 /// Copies a [`Template`] from the static [`TEMPLATES`] mutex based on a name and returns it
 pub fn copy_template(name: String, loc: usize) -> Result<Template, PangError> {
     let mutex = TEMPLATES.lock().unwrap();
-    let mut index = 0;
-    for (i, template) in mutex.iter().enumerate() {
-        if template.name == name {
-            index = i;
-            break;
-        }
-        if mutex.len()-1 == i {
-            return Err(PangError::TemplateNonExistent(name, loc))
-        }
+    match mutex.iter().find(|t| t.name == name) {
+        Some(template) => Ok(template.clone()),
+        None => Err(PangError::TemplateNonExistent(name, loc)),
+    }
+}
+
+/// Describes the schema of a [`Template`] and counts its instances
+pub fn describe_template(name: String, loc: usize) -> Result<Description, PangError> {
+    let inst_mutex = INSTANCES.lock().unwrap();
+    let temp_mutex = TEMPLATES.lock().unwrap();
+    match temp_mutex.iter().find(|t| t.name == name) {
+        Some(template) => Ok(Description::new(template, inst_mutex.iter().filter(|i| i.template == name).count())),
+        None => Err(PangError::TemplateNonExistent(name, loc)),
     }
-    Ok(mutex.get(index).unwrap().clone())
+}
+
+/// Copies a previous or the current version of a [`Template`]
+pub fn copy_template_version(name: String, version: usize, loc: usize) -> Result<Template, PangError> {
+    let current = copy_template(name.clone(), loc)?;
+    if current.version == version {
+        return Ok(current)
+    }
+    let history = HISTORY.lock().unwrap();
+    match history.iter().find(|t| t.name == name && t.version == version) {
+        Some(template) => Ok(template.clone()),
+        None => Err(PangError::VersionNonExistent(name, version, loc)),
+    }
+}
+
+/// Copies every version of a [`Template`], ordered from the first to the current one
+pub fn copy_template_history(name: String, loc: usize) -> Result<Vec<Template>, PangError> {
+    let current = copy_template(name.clone(), loc)?;
+    let mut versions: Vec<Template> = HISTORY.lock().unwrap().iter()
+        .filter(|t| t.name == name)
+        .cloned()
+        .collect();
+    versions.sort_by_key(|t| t.version);
+    versions.push(current);
+    Ok(versions)
+}
+
+/// Finds the [`Template`] of an [`Instance`]
+fn template_of<'a>(templates: &'a [Template], instance: &Instance, loc: usize) -> Result<&'a Template, PangError> {
+    templates.iter().find(|t| t.name == instance.template)
+        .ok_or_else(|| PangError::TemplateNonExistent(instance.template.clone(), loc))
 }
 
 /// Copies the whole [`TEMPLATES`] vec.
@@ -125,4 +909,1015 @@ pub fn copy_templates() -> Vec<Template> {
 pub fn copy_instances() -> Vec<Instance> {
     let mutex = INSTANCES.lock().unwrap();
     return mutex.clone();
-}
\ No newline at end of file
+}
+
+/// Copies the instances, that match the condition, ordered by a field if given.
+/// 
+/// The instances can be limited to a [`Template`], optionally including the instances of the templates extending it.
+/// Indexed fields of a single template narrow down the instances to check and give their order.
+pub fn find_instances(template: Option<(String, bool)>, condition: Option<Condition>, order: Option<(String, bool)>, loc: usize) -> Result<Vec<Instance>, PangError> {
+    let inst_mutex = INSTANCES.lock().unwrap();
+    let temp_mutex = TEMPLATES.lock().unwrap();
+    let indexes = INDEXES.lock().unwrap();
+    let (family, single, lookup) = select_instances(&temp_mutex, &indexes, template, condition.as_ref(), loc)?;
+    let candidates = lookup.map(|(candidates, _)| candidates);
+    let mut res = Vec::new();
+    for instance in inst_mutex.iter() {
+        if family.as_ref().is_some_and(|family| !family.contains(&instance.template)) {
+            continue
+        }
+        if candidates.as_ref().is_some_and(|candidates| !candidates.contains(&instance.name)) {
+            continue
+        }
+        if condition.as_ref().map_or(Ok(true), |condition| condition.matches(instance, loc))? {
+            res.push(instance.clone());
+        }
+    }
+    let (field, descending) = match order {
+        Some(order) => order,
+        None => return Ok(res),
+    };
+    match single.filter(|template| template.options.get(&field).is_some_and(|options| options.indexed)) {
+        Some(template) => {
+            let mut found: HashMap<String, Instance> = res.into_iter().map(|instance| (instance.name.clone(), instance)).collect();
+            res = indexes.ordered(&template.name, &field).into_iter().filter_map(|name| found.remove(&name)).collect();
+        },
+        None => {
+            let mut keyed = Vec::new();
+            for instance in res {
+                keyed.push((instance.get(&field, loc)?, instance));
+            }
+            // Values, that can't be compared, are treated as equal
+            keyed.sort_by(|(a, first), (b, second)| a.compare(b).unwrap_or(Ordering::Equal).then_with(|| first.name.cmp(&second.name)));
+            res = keyed.into_iter().map(|(_, instance)| instance).collect();
+        },
+    }
+    if descending {
+        res.reverse();
+    }
+    Ok(res)
+}
+
+/// Returns how a query over instances would find them and how many it would check at most, without copying any
+pub fn plan_instances(template: Option<(String, bool)>, condition: Option<&Condition>, order: Option<&String>, loc: usize) -> Result<(Access, usize), PangError> {
+    let inst_mutex = INSTANCES.lock().unwrap();
+    let temp_mutex = TEMPLATES.lock().unwrap();
+    let indexes = INDEXES.lock().unwrap();
+    let (family, single, lookup) = select_instances(&temp_mutex, &indexes, template, condition, loc)?;
+    let (rows, mut looked_up) = match lookup {
+        Some((candidates, looked_up)) => (candidates.len(), looked_up),
+        None => (inst_mutex.iter().filter(|i| family.as_ref().is_none_or(|family| family.contains(&i.template))).count(), Vec::new()),
+    };
+    // An indexed field gives the order without sorting
+    if let Some(field) = order.filter(|field| single.is_some_and(|t| t.options.get(*field).is_some_and(|options| options.indexed))) {
+        looked_up.push(field.clone());
+    }
+    let mut fields = Vec::new();
+    for field in looked_up {
+        if !fields.contains(&field) {
+            fields.push(field);
+        }
+    }
+    Ok(match single {
+        Some(template) if !fields.is_empty() => (Access::Index { template: template.name.clone(), fields }, rows),
+        _ => (Access::Scan(family.unwrap_or_default()), rows),
+    })
+}
+
+/// Templates a query over instances is limited to, the single one if its indexes can be used
+/// and the instances and fields the indexes narrow the condition down to
+type Selection<'a> = (Option<Vec<String>>, Option<&'a Template>, Option<Lookup>);
+
+/// Selects the templates and indexed instances of a query over instances, see [`Selection`]
+fn select_instances<'a>(templates: &'a [Template], indexes: &OrderedIndex, template: Option<(String, bool)>, condition: Option<&Condition>, loc: usize)
+    -> Result<Selection<'a>, PangError> {
+    let family = match template {
+        Some((name, children)) => Some(template_family(templates, name, children, loc)?),
+        None => None,
+    };
+    // Only the indexes of a single template can be used
+    let single = family.as_ref()
+        .filter(|family| family.len() == 1)
+        .and_then(|family| templates.iter().find(|t| t.name == family[0]));
+    let lookup = match (single, condition) {
+        (Some(template), Some(condition)) => indexes.lookup(template, condition),
+        _ => None,
+    };
+    Ok((family, single, lookup))
+}
+
+/// Names of a [`Template`] and optionally the templates extending it
+fn template_family(templates: &[Template], name: String, children: bool, loc: usize) -> Result<Vec<String>, PangError> {
+    if !templates.iter().any(|t| t.name == name) {
+        return Err(PangError::TemplateNonExistent(name, loc))
+    }
+    let mut family = vec![name];
+    // Collect the children of the children as well
+    let mut i = 0;
+    while children && i < family.len() {
+        let parent = family[i].clone();
+        family.extend(templates.iter().filter(|t| t.parent.as_ref() == Some(&parent)).map(|t| t.name.clone()));
+        i += 1;
+    }
+    Ok(family)
+}
+
+/// Adds or removes the ordered index of fields of a [`Template`]
+pub fn index_fields(name: String, fields: Vec<String>, indexed: bool, loc: usize) -> Result<(), PangError> {
+    let instances = INSTANCES.lock().unwrap();
+    let mut templates = TEMPLATES.lock().unwrap();
+    let template = templates.iter().find(|t| t.name == name).ok_or_else(|| PangError::TemplateNonExistent(name.clone(), loc))?;
+    for field in &fields {
+        if get_path(&template.data, field, loc)?.data_type == DataType::OBJECT {
+            return Err(PangError::TypeMismatch(loc))
+        }
+    }
+    // Only the instances of the template are indexed again
+    let mut indexes = INDEXES.lock().unwrap();
+    let affected: Vec<&Instance> = instances.iter().filter(|i| i.template == name).collect();
+    for instance in affected.iter() {
+        indexes.unindex(&templates, instance);
+    }
+    let template = templates.iter_mut().find(|t| t.name == name).unwrap();
+    for field in fields {
+        let options = template.options.entry(field.clone()).or_insert_with(FieldOptions::default);
+        options.indexed = indexed;
+        if options == &FieldOptions::default() {
+            template.options.remove(&field);
+        }
+    }
+    for instance in affected {
+        indexes.index(&templates, instance);
+    }
+    Ok(())
+}
+
+/// Checks that nesting the [`Template`] `nested` inside of `root` doesn't create a cycle.
+pub fn check_nesting(root: &str, nested: &str, loc: usize) -> Result<(), PangError> {
+    let mutex = TEMPLATES.lock().unwrap();
+    let mut stack = vec![nested.to_string()];
+    while let Some(name) = stack.pop() {
+        if name == root {
+            return Err(PangError::NestingCycle(root.to_string(), loc))
+        }
+        if let Some(template) = mutex.iter().find(|t| t.name == name) {
+            stack.extend(template.options.values().filter_map(|o| o.template.clone()));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use crate::{lexer, query};
+
+    /// Runs a request like the server does, the stores are shared, so every test uses its own names
+    fn run(source: &str) -> Value {
+        serde_json::from_str(&query::data(lexer::parse(source.to_string()), &Value::Null)).unwrap()
+    }
+
+    #[test]
+    fn nested_fields_by_path() {
+        let res = run(r#"
+            TEMPLATE "Addr26"; STRING "City" VALUE "x"; END "Addr26";
+            TEMPLATE "Person26"; "Home" TYPE "Addr26"; END "Person26";
+            CREATE "p26" TEMPLATE "Person26";
+            SELECT "p26"; SET "Home.City" VALUE "Rome"; GET "Home.City"; END "p26";
+            QUERY "p26" FROM INSTANCE;
+        "#);
+        assert_eq!(res[0][0]["Data"]["Home.City"], "Rome");
+        assert_eq!(res[1][0]["Instance"]["data"]["Home"]["City"], "Rome");
+
+        let res = run(r#"SELECT "p26"; SET "Home.Street" VALUE "a"; END "p26";"#);
+        assert_eq!(res["Code"], 0b0010);
+        let res = run(r#"SELECT "p26"; SET "Home.City" VALUE 1; END "p26";"#);
+        assert_eq!(res["Code"], 0b0001);
+    }
+
+    #[test]
+    fn nesting_cycle() {
+        let res = run(r#"TEMPLATE "Loop26"; "Self" TYPE "Loop26"; END "Loop26";"#);
+        assert_eq!(res["Code"], 0b1011);
+
+        let res = run(r#"
+            TEMPLATE "Inner26"; STRING "Name"; END "Inner26";
+            TEMPLATE "Outer26"; "Inner" TYPE "Inner26"; END "Outer26";
+            ALTER "Inner26"; ADD "Outer" TYPE "Outer26"; END "Inner26";
+        "#);
+        assert_eq!(res["Code"], 0b1011);
+    }
+
+    #[test]
+    fn references_on_delete() {
+        let res = run(r#"
+            TEMPLATE "Owner27"; STRING "Name"; END "Owner27";
+            TEMPLATE "Car27"; "Owner" REF "Owner27"; END "Car27";
+            TEMPLATE "Pet27"; "Owner" REF "Owner27" ON DELETE CASCADE; END "Pet27";
+            TEMPLATE "Note27"; "Owner" REF "Owner27" ON DELETE SET NULL; END "Note27";
+            CREATE "o27" TEMPLATE "Owner27";
+            CREATE "car27" TEMPLATE "Car27";
+            SELECT "car27"; SET "Owner" VALUE "o27"; END "car27";
+            CREATE "pet27" TEMPLATE "Pet27";
+            SELECT "pet27"; SET "Owner" VALUE "o27"; END "pet27";
+            CREATE "note27" TEMPLATE "Note27";
+            SELECT "note27"; SET "Owner" VALUE "o27"; END "note27";
+        "#);
+        assert_eq!(res, Value::Array(Vec::new()));
+
+        let res = run(r#"SELECT "car27"; SET "Owner" VALUE "nobody27"; END "car27";"#);
+        assert_eq!(res["Code"], 0b0110);
+
+        // RESTRICT keeps the owner and everything referencing it
+        let res = run(r#"DELETE "o27" FROM INSTANCE;"#);
+        assert_eq!(res["Code"], 0b0111);
+        let res = run(r#"QUERY "o27" FROM INSTANCE; QUERY "pet27" FROM INSTANCE;"#);
+        assert_eq!(res[0][0]["Instance"]["name"], "o27");
+        assert_eq!(res[1][0]["Instance"]["name"], "pet27");
+
+        let res = run(r#"
+            DELETE "car27" FROM INSTANCE;
+            DELETE "o27" FROM INSTANCE;
+            QUERY "note27" FROM INSTANCE;
+        "#);
+        assert_eq!(res[2][0]["Instance"]["data"]["Owner"], "");
+        assert_eq!(res[2][0]["Instance"]["revision"], 3);
+        let res = run(r#"QUERY "pet27" FROM INSTANCE;"#);
+        assert_eq!(res["Code"], 0b0110);
+    }
+
+    #[test]
+    fn cascade_removes_restricting() {
+        let res = run(r#"
+            TEMPLATE "Owner27c"; STRING "Name"; END "Owner27c";
+            TEMPLATE "Tag27c"; "Owner" REF "Owner27c" ON DELETE CASCADE; END "Tag27c";
+            TEMPLATE "Pet27c"; "Owner" REF "Owner27c" ON DELETE CASCADE; END "Pet27c";
+            TEMPLATE "Chip27c"; "Pet" REF "Pet27c"; "Tag" REF "Tag27c" ON DELETE CASCADE; END "Chip27c";
+            TEMPLATE "Note27c"; "Pet" REF "Pet27c" ON DELETE SET NULL; END "Note27c";
+            CREATE "o27c" TEMPLATE "Owner27c";
+            CREATE "tag27c" TEMPLATE "Tag27c";
+            SELECT "tag27c"; SET "Owner" VALUE "o27c"; END "tag27c";
+            CREATE "pet27c" TEMPLATE "Pet27c";
+            SELECT "pet27c"; SET "Owner" VALUE "o27c"; END "pet27c";
+            CREATE "chip27c" TEMPLATE "Chip27c";
+            SELECT "chip27c"; SET "Pet" VALUE "pet27c"; SET "Tag" VALUE "tag27c"; END "chip27c";
+            CREATE "note27c" TEMPLATE "Note27c";
+            SELECT "note27c"; SET "Pet" VALUE "pet27c"; END "note27c";
+        "#);
+        assert_eq!(res, Value::Array(Vec::new()));
+
+        // The chip restricting the pet is removed by the same delete, the note stays
+        let res = run(r#"
+            DELETE "o27c" FROM INSTANCE;
+            QUERY "note27c" FROM INSTANCE;
+        "#);
+        assert_eq!(res[1][0]["Instance"]["data"]["Pet"], "");
+        for name in ["pet27c", "chip27c"] {
+            let res = run(&format!(r#"QUERY "{}" FROM INSTANCE;"#, name));
+            assert_eq!(res["Code"], 0b0110, "{}", name);
+        }
+    }
+
+    #[test]
+    fn referenced_template_is_kept() {
+        let res = run(r#"
+            TEMPLATE "Target27"; STRING "Name"; END "Target27";
+            TEMPLATE "Source27"; "Target" REF "Target27"; END "Source27";
+            DELETE "Target27" FROM TEMPLATE;
+        "#);
+        assert_eq!(res["Code"], 0b1000);
+
+        let res = run(r#"
+            DELETE "Source27" FROM TEMPLATE;
+            DELETE "Target27" FROM TEMPLATE;
+        "#);
+        assert_eq!(res[1][0]["Template"]["name"], "Target27");
+    }
+
+    #[test]
+    fn enum_values() {
+        let res = run(r#"
+            TEMPLATE "Shirt28"; ENUM "Size" OF ("S", "M", "L") VALUE "M"; END "Shirt28";
+            CREATE "s28" TEMPLATE "Shirt28";
+            SELECT "s28"; SET "Size" VALUE "L"; END "s28";
+            QUERY "s28" FROM INSTANCE;
+        "#);
+        assert_eq!(res[0][0]["Instance"]["data"]["Size"], "L");
+
+        let res = run(r#"SELECT "s28"; SET "Size" VALUE "XL"; END "s28";"#);
+        assert_eq!(res["Code"], 0b0011);
+        let res = run(r#"TEMPLATE "Bad28"; ENUM "Size" OF ("S", "M") VALUE "L"; END "Bad28";"#);
+        assert_eq!(res["Code"], 0b0011);
+    }
+
+    #[test]
+    fn field_constraints() {
+        let res = run(r#"
+            TEMPLATE "Limits29";
+            INTEGER "Age" VALUE 3 MIN 1 MAX 150;
+            STRING "Name" VALUE "n" MAXLEN 3 NOT EMPTY;
+            STRING "Code" VALUE "A" MATCHES "^[A-Z]+$";
+            END "Limits29";
+            CREATE "l29" TEMPLATE "Limits29";
+        "#);
+        assert_eq!(res, Value::Array(Vec::new()));
+
+        for set in [r#"SET "Age" VALUE 0;"#, r#"SET "Age" VALUE 151;"#, r#"SET "Name" VALUE "long";"#,
+            r#"SET "Name" VALUE "";"#, r#"SET "Code" VALUE "abc";"#] {
+            let res = run(&format!(r#"SELECT "l29"; {} END "l29";"#, set));
+            assert_eq!(res["Code"], 0b10001, "{}", set);
+        }
+
+        let res = run(r#"
+            SELECT "l29"; SET "Age" VALUE 150; SET "Code" VALUE "XYZ"; END "l29";
+            QUERY "l29" FROM INSTANCE;
+        "#);
+        assert_eq!(res[0][0]["Instance"]["data"], serde_json::json!({ "Age": 150, "Name": "n", "Code": "XYZ" }));
+    }
+
+    #[test]
+    fn negative_constraints() {
+        let res = run(r#"
+            TEMPLATE "Below29"; INTEGER "Depth" VALUE -10 MIN -20 MAX -5; FLOAT "Temp" VALUE -1.5 MIN -40.0 MAX -0.5; END "Below29";
+            CREATE "b29" TEMPLATE "Below29";
+            SELECT "b29"; SET "Depth" VALUE -20; SET "Temp" VALUE -0.5; END "b29";
+            QUERY "b29" FROM INSTANCE;
+        "#);
+        assert_eq!(res[0][0]["Instance"]["data"], serde_json::json!({ "Depth": -20, "Temp": -0.5 }));
+
+        for set in [r#"SET "Depth" VALUE -21;"#, r#"SET "Depth" VALUE -4;"#, r#"SET "Temp" VALUE 0.0;"#] {
+            let res = run(&format!(r#"SELECT "b29"; {} END "b29";"#, set));
+            assert_eq!(res["Code"], 0b10001, "{}", set);
+        }
+    }
+
+    #[test]
+    fn invalid_constraints() {
+        // The default value has to satisfy the constraints as well
+        let res = run(r#"TEMPLATE "Default29"; STRING "Code" VALUE "a" MATCHES "^[A-Z]+$"; END "Default29";"#);
+        assert_eq!(res["Code"], 0b10001);
+        let res = run(r#"TEMPLATE "Pattern29"; STRING "Code" VALUE "A" MATCHES "(["; END "Pattern29";"#);
+        assert_eq!(res["Code"], 0b1111);
+    }
+
+    #[test]
+    fn unique_values() {
+        run(r#"
+            TEMPLATE "User30"; STRING "Mail" UNIQUE; END "User30";
+            CREATE "a30" TEMPLATE "User30";
+            SELECT "a30"; SET "Mail" VALUE "a@x"; END "a30";
+            CREATE "b30" TEMPLATE "User30";
+        "#);
+        let res = run(r#"SELECT "b30"; SET "Mail" VALUE "a@x"; END "b30";"#);
+        assert_eq!(res["Code"], 0b10100);
+        let res = run(r#"QUERY "b30" FROM INSTANCE;"#);
+        assert_eq!(res[0][0]["Instance"]["data"]["Mail"], "");
+
+        // Changing or deleting an instance frees its value
+        let res = run(r#"
+            SELECT "a30"; SET "Mail" VALUE "c@x"; END "a30";
+            SELECT "b30"; SET "Mail" VALUE "a@x"; END "b30";
+            DELETE "b30" FROM INSTANCE;
+            CREATE "d30" TEMPLATE "User30";
+            SELECT "d30"; SET "Mail" VALUE "a@x"; END "d30";
+        "#);
+        assert_eq!(res[0][0]["Instance"]["name"], "b30");
+        let res = run(r#"SELECT "d30"; SET "Mail" VALUE "c@x"; END "d30";"#);
+        assert_eq!(res["Code"], 0b10100);
+    }
+
+    #[test]
+    fn alter_migrates_instances() {
+        let res = run(r#"
+            TEMPLATE "Book31"; STRING "Title" VALUE "t"; INTEGER "Pages"; END "Book31";
+            CREATE "b31" TEMPLATE "Book31";
+            SELECT "b31"; SET "Title" VALUE "Dune"; END "b31";
+            ALTER "Book31"; ADD INTEGER "Year" VALUE 1965; DROP "Pages"; RENAME "Title" TO "Name"; END "Book31";
+            QUERY "b31" FROM INSTANCE;
+        "#);
+        let instance = &res[0][0]["Instance"];
+        assert_eq!(instance["data"], serde_json::json!({ "Name": "Dune", "Year": 1965 }));
+        assert_eq!(instance["version"], 2);
+
+        let res = run(r#"ALTER "Book31"; DROP "Nope"; END "Book31";"#);
+        assert_eq!(res["Code"], 0b0010);
+        let res = run(r#"ALTER "Book31"; ADD STRING "Name"; END "Book31";"#);
+        assert_eq!(res["Code"], 0b10000);
+    }
+
+    #[test]
+    fn change_converts_or_fails_as_a_whole() {
+        let res = run(r#"
+            TEMPLATE "Day32"; STRING "Day" VALUE "1"; END "Day32";
+            CREATE "mon32" TEMPLATE "Day32";
+            CREATE "tue32" TEMPLATE "Day32";
+            SELECT "tue32"; SET "Day" VALUE "two"; END "tue32";
+            ALTER "Day32" DRY RUN; CHANGE "Day" TO INTEGER; END "Day32";
+        "#);
+        assert_eq!(res[0][0]["Failure"]["instance"], "tue32");
+        assert_eq!(res[0][0]["Failure"]["error"]["Code"], 0b10010);
+
+        // No instance is converted, if one of them can't be
+        let res = run(r#"ALTER "Day32"; CHANGE "Day" TO INTEGER; END "Day32";"#);
+        assert_eq!(res["Code"], 0b10010);
+        let res = run(r#"QUERY "mon32" FROM INSTANCE;"#);
+        assert_eq!(res[0][0]["Instance"]["data"]["Day"], "1");
+
+        let res = run(r#"
+            SELECT "tue32"; SET "Day" VALUE "2"; END "tue32";
+            ALTER "Day32"; CHANGE "Day" TO INTEGER; END "Day32";
+            QUERY "mon32" FROM INSTANCE;
+            QUERY "tue32" FROM INSTANCE;
+        "#);
+        assert_eq!(res[0][0]["Instance"]["data"]["Day"], 1);
+        assert_eq!(res[1][0]["Instance"]["data"]["Day"], 2);
+        assert_eq!(res[0][0]["Instance"]["version"], 2);
+    }
+
+    #[test]
+    fn deleting_a_template_removes_its_instances() {
+        let res = run(r#"
+            TEMPLATE "Kept33"; STRING "Name"; END "Kept33";
+            TEMPLATE "Gone33"; STRING "Name"; END "Gone33";
+            CREATE "k33" TEMPLATE "Kept33";
+            CREATE "g33" TEMPLATE "Gone33";
+            CREATE "h33" INSTANCE "g33";
+            DELETE "Gone33" FROM TEMPLATE;
+            QUERY "k33" FROM INSTANCE;
+        "#);
+        assert_eq!(res[1][0]["Instance"]["template"], "Kept33");
+
+        for name in ["g33", "h33"] {
+            let res = run(&format!(r#"QUERY "{}" FROM INSTANCE;"#, name));
+            assert_eq!(res["Code"], 0b0110);
+        }
+    }
+
+    #[test]
+    fn template_history() {
+        let res = run(r#"
+            TEMPLATE "Doc34"; STRING "Title"; END "Doc34";
+            ALTER "Doc34"; ADD INTEGER "Pages"; END "Doc34";
+            ALTER "Doc34"; RENAME "Pages" TO "Size"; END "Doc34";
+            QUERY "Doc34" FROM TEMPLATE VERSION 2;
+            QUERY "Doc34" FROM TEMPLATE HISTORY;
+            QUERY "Doc34" FROM TEMPLATE;
+        "#);
+        assert_eq!(res[0][0]["Template"]["data"], serde_json::json!({ "Title": "", "Pages": 0 }));
+        let versions: Vec<&Value> = res[1].as_array().unwrap().iter().map(|t| &t["Template"]["version"]).collect();
+        assert_eq!(versions, [1, 2, 3]);
+        assert_eq!(res[2][0]["Template"]["data"], serde_json::json!({ "Title": "", "Size": 0 }));
+
+        let res = run(r#"QUERY "Doc34" FROM TEMPLATE VERSION 9;"#);
+        assert_eq!(res["Code"], 0b11000);
+    }
+
+    #[test]
+    fn inherited_fields() {
+        let res = run(r#"
+            TEMPLATE "Event35"; STRING "Owner" VALUE "nobody"; INTEGER "Start" VALUE 1 MIN 1; END "Event35";
+            TEMPLATE "Meeting35" EXTENDS "Event35"; STRING "Room"; END "Meeting35";
+            CREATE "e35" TEMPLATE "Event35";
+            CREATE "m35" TEMPLATE "Meeting35";
+            QUERY INSTANCE OF "Event35";
+            QUERY INSTANCE OF "Event35" WITH CHILDREN;
+            ALTER "Event35"; ADD STRING "Id"; END "Event35";
+            QUERY "m35" FROM INSTANCE;
+        "#);
+        assert_eq!(res[0].as_array().unwrap().len(), 1);
+        assert_eq!(res[1].as_array().unwrap().len(), 2);
+        // Altering the parent migrates the instances of its children
+        assert_eq!(res[2][0]["Instance"]["data"], serde_json::json!({ "Owner": "nobody", "Start": 1, "Room": "", "Id": "" }));
+
+        let res = run(r#"SELECT "m35"; SET "Start" VALUE 0; END "m35";"#);
+        assert_eq!(res["Code"], 0b10001);
+        let res = run(r#"TEMPLATE "Bad35" EXTENDS "Event35"; STRING "Owner"; END "Bad35";"#);
+        assert_eq!(res["Code"], 0b10000);
+    }
+
+    #[test]
+    fn describe_schema() {
+        let res = run(r#"
+            TEMPLATE "Addr36"; STRING "City" VALUE "x" NOT EMPTY; END "Addr36";
+            TEMPLATE "Person36"; STRING "Name" UNIQUE; INTEGER "Age" VALUE 3 MIN 0; "Home" TYPE "Addr36"; END "Person36";
+            CREATE "p36" TEMPLATE "Person36";
+            DESCRIBE "Person36";
+        "#);
+        let description = &res[0][0]["Description"];
+        assert_eq!(description["instances"], 1);
+        let fields: Vec<&Value> = description["fields"].as_array().unwrap().iter().map(|f| &f["name"]).collect();
+        assert_eq!(fields, ["Name", "Age", "Home", "Home.City"]);
+        assert_eq!(description["fields"][1]["options"]["min"], 0);
+        assert_eq!(description["fields"][3]["options"]["not_empty"], true);
+
+        let res = run(r#"DESCRIBE "Nope36";"#);
+        assert_eq!(res["Code"], 0b1010);
+    }
+
+    #[test]
+    fn starting_values() {
+        let res = run(r#"
+            TEMPLATE "Addr37"; STRING "City" VALUE "Sample" STARTING "Berlin"; END "Addr37";
+            TEMPLATE "Day37"; INTEGER "Day" VALUE 5 MIN 1 STARTING 1; "Home" TYPE "Addr37"; END "Day37";
+            CREATE "d37" TEMPLATE "Day37";
+            QUERY "d37" FROM INSTANCE;
+            SELECT "d37"; SET "Day" VALUE 7; SET "Home.City" VALUE "Rome"; END "d37";
+            SELECT "d37"; SET "Day" VALUE DEFAULT; SET "Home.City" VALUE DEFAULT; END "d37";
+            QUERY "d37" FROM INSTANCE;
+        "#);
+        let starting = serde_json::json!({ "Day": 1, "Home": { "City": "Berlin" } });
+        assert_eq!(res[0][0]["Instance"]["data"], starting);
+        assert_eq!(res[1][0]["Instance"]["data"], starting);
+
+        let res = run(r#"TEMPLATE "Min37"; INTEGER "A" MIN 1 STARTING 0; END "Min37";"#);
+        assert_eq!(res["Code"], 0b10001);
+        let res = run(r#"TEMPLATE "Type37"; INTEGER "A" STARTING "zero"; END "Type37";"#);
+        assert_eq!(res["Code"], 0b0001);
+    }
+
+    #[test]
+    fn added_fields_start_like_new_instances() {
+        let res = run(r#"
+            TEMPLATE "Addr37a"; STRING "City" VALUE "Sample" STARTING "Berlin"; END "Addr37a";
+            TEMPLATE "Day37a"; STRING "Name"; END "Day37a";
+            CREATE "old37a" TEMPLATE "Day37a";
+            ALTER "Day37a"; ADD INTEGER "S" VALUE 5 STARTING 1; ADD INTEGER "V" VALUE 3; ADD "Home" TYPE "Addr37a"; END "Day37a";
+            CREATE "new37a" TEMPLATE "Day37a";
+            QUERY "old37a" FROM INSTANCE;
+            QUERY "new37a" FROM INSTANCE;
+            QUERY "Day37a" FROM TEMPLATE;
+        "#);
+        let starting = serde_json::json!({ "Name": "", "S": 1, "V": 3, "Home": { "City": "Berlin" } });
+        assert_eq!(res[0][0]["Instance"]["data"], starting);
+        assert_eq!(res[1][0]["Instance"]["data"], starting);
+        assert_eq!(res[2][0]["Template"]["data"], serde_json::json!({ "Name": "", "S": 5, "V": 3, "Home": { "City": "Sample" } }));
+    }
+
+    #[test]
+    fn computed_fields() {
+        let res = run(r#"
+            TEMPLATE "Order38";
+            FLOAT "Price" VALUE 2.5;
+            INTEGER "Qty" VALUE 2;
+            FLOAT "Total" AS "Price" * "Qty" + 1;
+            STRING "First";
+            STRING "Last" VALUE "L";
+            STRING "Full" AS "First" + "Last";
+            END "Order38";
+            CREATE "o38" TEMPLATE "Order38";
+            SELECT "o38"; SET "Qty" VALUE 4; SET "First" VALUE "Ada"; GET "Total"; END "o38";
+            QUERY "o38" FROM INSTANCE;
+        "#);
+        assert_eq!(res[0][0]["Data"]["Total"], 11.0);
+        assert_eq!(res[1][0]["Instance"]["data"]["Full"], "AdaL");
+
+        let res = run(r#"SELECT "o38"; SET "Total" VALUE 1.0; END "o38";"#);
+        assert_eq!(res["Code"], 0b10011);
+        let res = run(r#"TEMPLATE "Cycle38"; INTEGER "A" AS "B" + 1; INTEGER "B" AS "A" + 1; END "Cycle38";"#);
+        assert_eq!(res["Code"], 0b100000);
+        let res = run(r#"TEMPLATE "Type38"; INTEGER "A" VALUE 1; INTEGER "B" AS "A" * 1.5; END "Type38";"#);
+        assert_eq!(res["Code"], 0b0001);
+    }
+
+    #[test]
+    fn computed_division_by_zero() {
+        let res = run(r#"
+            TEMPLATE "Avg38"; INTEGER "Sum"; INTEGER "Cnt"; INTEGER "Avg" AS "Sum" / "Cnt"; FLOAT "Ratio" AS "Sum" / "Cnt"; END "Avg38";
+            CREATE "avg38" TEMPLATE "Avg38";
+            QUERY "avg38" FROM INSTANCE;
+            SELECT "avg38"; SET "Sum" VALUE 10; SET "Cnt" VALUE 4; END "avg38";
+            QUERY "avg38" FROM INSTANCE;
+        "#);
+        assert_eq!(res[0][0]["Instance"]["data"], serde_json::json!({ "Sum": 0, "Cnt": 0, "Avg": 0, "Ratio": 0.0 }));
+        assert_eq!(res[1][0]["Instance"]["data"], serde_json::json!({ "Sum": 10, "Cnt": 4, "Avg": 2, "Ratio": 2.0 }));
+
+        // Only computed fields fall back to zero, a set expression still fails
+        let res = run(r#"SELECT "avg38"; SET "Cnt" VALUE 0; SET "Sum" VALUE "Sum" / "Cnt"; END "avg38";"#);
+        assert_eq!(res["Code"], 0b100010);
+    }
+
+    #[test]
+    fn create_with_values() {
+        let res = run(r#"
+            TEMPLATE "Day39"; STRING "First"; STRING "Second" STARTING "x"; INTEGER "Day" MAX 7; INTEGER "Next" AS "Day" + 1; END "Day39";
+            CREATE "mon39" TEMPLATE "Day39" WITH SET "First" VALUE "Science" SET "Day" VALUE 1;
+            CREATE "tue39" INSTANCE "mon39" WITH SET "Day" VALUE 2 SET "First" VALUE DEFAULT;
+            QUERY "mon39" FROM INSTANCE;
+            QUERY "tue39" FROM INSTANCE;
+        "#);
+        assert_eq!(res[0][0]["Instance"]["data"], serde_json::json!({ "First": "Science", "Second": "x", "Day": 1, "Next": 2 }));
+        assert_eq!(res[1][0]["Instance"]["data"], serde_json::json!({ "First": "", "Second": "x", "Day": 2, "Next": 3 }));
+
+        // An invalid value doesn't leave a half created instance behind
+        let res = run(r#"CREATE "wed39" TEMPLATE "Day39" WITH SET "First" VALUE "a" SET "Day" VALUE 9;"#);
+        assert_eq!(res["Code"], 0b10001);
+        let res = run(r#"CREATE "wed39" TEMPLATE "Day39" WITH SET "Next" VALUE 3;"#);
+        assert_eq!(res["Code"], 0b10011);
+        let res = run(r#"QUERY "wed39" FROM INSTANCE;"#);
+        assert_eq!(res["Code"], 0b0110);
+
+        // The values can be separated by commas
+        let res = run(r#"
+            CREATE "thu39" TEMPLATE "Day39" WITH SET "First" VALUE "Math", SET "Day" VALUE 4;
+            QUERY "thu39" FROM INSTANCE;
+        "#);
+        assert_eq!(res[0][0]["Instance"]["data"], serde_json::json!({ "First": "Math", "Second": "x", "Day": 4, "Next": 5 }));
+    }
+
+    #[test]
+    fn create_races_delete() {
+        run(r#"
+            TEMPLATE "Owner39"; STRING "Name"; END "Owner39";
+            TEMPLATE "Car39"; "Owner" REF "Owner39"; END "Car39";
+        "#);
+        for i in 0..50 {
+            run(&format!(r#"CREATE "o39-{}" TEMPLATE "Owner39";"#, i));
+            let create = std::thread::spawn(move || run(&format!(r#"CREATE "c39-{0}" TEMPLATE "Car39" WITH SET "Owner" VALUE "o39-{0}";"#, i)));
+            let delete = std::thread::spawn(move || run(&format!(r#"DELETE "o39-{}" FROM INSTANCE;"#, i)));
+            create.join().unwrap();
+            delete.join().unwrap();
+            // Either the car was created first and restricts the deletion, or it was refused
+            let car = run(&format!(r#"QUERY "c39-{}" FROM INSTANCE;"#, i));
+            let owner = run(&format!(r#"QUERY "o39-{}" FROM INSTANCE;"#, i));
+            assert_eq!(car["Code"].is_null(), owner["Code"].is_null(), "{}", i);
+        }
+    }
+
+    #[test]
+    fn conditional_actions() {
+        let res = run(r#"
+            TEMPLATE "Day40" IF NOT EXISTS; STRING "First"; INTEGER "Day" MAX 7; END "Day40";
+            TEMPLATE "Day40" IF NOT EXISTS; STRING "Other"; END "Day40";
+            CREATE "mon40" TEMPLATE "Day40" IF NOT EXISTS WITH SET "Day" VALUE 1;
+            CREATE "mon40" TEMPLATE "Day40" IF NOT EXISTS WITH SET "Day" VALUE 2;
+            UPSERT "tue40" TEMPLATE "Day40" WITH SET "Day" VALUE 2;
+            UPSERT "tue40" TEMPLATE "Day40" WITH SET "First" VALUE "x";
+            DELETE "wed40" FROM INSTANCE IF EXISTS;
+            QUERY "mon40" FROM INSTANCE;
+            QUERY "tue40" FROM INSTANCE;
+        "#);
+        let actions: Vec<&Value> = res.as_array().unwrap()[..7].iter().map(|r| &r[0]["Action"]["action"]).collect();
+        assert_eq!(actions, ["CREATED", "SKIPPED", "CREATED", "SKIPPED", "CREATED", "UPDATED", "SKIPPED"]);
+        assert_eq!(res[7][0]["Instance"]["data"], serde_json::json!({ "First": "", "Day": 1 }));
+        assert_eq!(res[8][0]["Instance"]["data"], serde_json::json!({ "First": "x", "Day": 2 }));
+
+        let res = run(r#"UPSERT "tue40" TEMPLATE "Day40" WITH SET "Day" VALUE 9;"#);
+        assert_eq!(res["Code"], 0b10001);
+        let res = run(r#"CREATE "tue40" TEMPLATE "Day40";"#);
+        assert_eq!(res["Code"], 0b0101);
+    }
+
+    #[test]
+    fn concurrent_upserts() {
+        run(r#"TEMPLATE "Counter40"; INTEGER "Hits"; END "Counter40";"#);
+        let threads: Vec<_> = (0..8).map(|_| std::thread::spawn(||
+            run(r#"UPSERT "c40" TEMPLATE "Counter40" WITH SET "Hits" ADD 1;"#)[0][0]["Action"]["action"].clone()
+        )).collect();
+        let actions: Vec<Value> = threads.into_iter().map(|thread| thread.join().unwrap()).collect();
+        assert_eq!(actions.iter().filter(|action| *action == "CREATED").count(), 1);
+        let res = run(r#"QUERY "c40" FROM INSTANCE;"#);
+        assert_eq!(res[0][0]["Instance"]["data"]["Hits"], 8);
+    }
+
+    #[test]
+    fn bulk_update() {
+        let res = run(r#"
+            TEMPLATE "Day41"; INTEGER "Day" MAX 7; FLOAT "Seconds" VALUE 1.5; STRING "Code" UNIQUE; END "Day41";
+            CREATE "mon41" TEMPLATE "Day41" WITH SET "Day" VALUE 1 SET "Code" VALUE "mon";
+            CREATE "sat41" TEMPLATE "Day41" WITH SET "Day" VALUE 6 SET "Code" VALUE "sat";
+            CREATE "sun41" TEMPLATE "Day41" WITH SET "Day" VALUE 7 SET "Code" VALUE "sun";
+            UPDATE INSTANCE OF "Day41" SET "Seconds" VALUE 0.0 WHERE "Day" > 5;
+            QUERY INSTANCE OF "Day41";
+        "#);
+        assert_eq!(res[0][0]["Count"], 2);
+        let seconds: Vec<&Value> = res[1].as_array().unwrap().iter().map(|i| &i["Instance"]["data"]["Seconds"]).collect();
+        assert_eq!(seconds, [1.5, 0.0, 0.0]);
+
+        // The values can be separated by commas
+        let res = run(r#"
+            UPDATE INSTANCE OF "Day41" SET "Seconds" VALUE 2.0, SET "Day" VALUE 5 WHERE "Code" = "sun";
+            QUERY "sun41" FROM INSTANCE;
+        "#);
+        assert_eq!(res[0][0]["Count"], 1);
+        assert_eq!(res[1][0]["Instance"]["data"], serde_json::json!({ "Day": 5, "Seconds": 2.0, "Code": "sun" }));
+    }
+
+    #[test]
+    fn bulk_update_rolls_back() {
+        let before = run(r#"
+            TEMPLATE "Day41r"; INTEGER "Day" MAX 7; STRING "Code" UNIQUE; END "Day41r";
+            CREATE "mon41r" TEMPLATE "Day41r" WITH SET "Day" VALUE 1 SET "Code" VALUE "mon";
+            CREATE "sat41r" TEMPLATE "Day41r" WITH SET "Day" VALUE 6 SET "Code" VALUE "sat";
+            CREATE "sun41r" TEMPLATE "Day41r" WITH SET "Day" VALUE 7 SET "Code" VALUE "sun";
+            QUERY INSTANCE OF "Day41r";
+        "#);
+
+        let res = run(r#"UPDATE INSTANCE OF "Day41r" SET "Day" VALUE 8 WHERE "Day" >= 6;"#);
+        assert_eq!(res["Code"], 0b10001);
+        // The second instance conflicts with the first one, after that was already updated
+        let res = run(r#"UPDATE INSTANCE OF "Day41r" SET "Code" VALUE "same" WHERE "Day" >= 6;"#);
+        assert_eq!(res["Code"], 0b10100);
+        assert_eq!(run(r#"QUERY INSTANCE OF "Day41r";"#)[0], before[0]);
+
+        // The unique index still holds the old values
+        let res = run(r#"SELECT "mon41r"; SET "Code" VALUE "sat"; END "mon41r";"#);
+        assert_eq!(res["Code"], 0b10100);
+        let res = run(r#"SELECT "mon41r"; SET "Code" VALUE "same"; END "mon41r";"#);
+        assert_eq!(res, Value::Array(Vec::new()));
+    }
+
+    #[test]
+    fn bulk_delete() {
+        let res = run(r#"
+            TEMPLATE "Day42"; INTEGER "Day"; END "Day42";
+            TEMPLATE "Note42"; "Day" REF "Day42" ON DELETE CASCADE; END "Note42";
+            CREATE "mon42" TEMPLATE "Day42" WITH SET "Day" VALUE 1;
+            CREATE "sat42" TEMPLATE "Day42" WITH SET "Day" VALUE 6;
+            CREATE "sun42" TEMPLATE "Day42" WITH SET "Day" VALUE 7;
+            CREATE "n42" TEMPLATE "Note42" WITH SET "Day" VALUE "sun42";
+            DELETE INSTANCE OF "Day42" WHERE "Day" > 6;
+            QUERY INSTANCE OF "Note42";
+        "#);
+        assert_eq!(res[0][0]["Instance"]["name"], "sun42");
+        assert_eq!(res[1], Value::Array(Vec::new()));
+
+        // Deleting every instance has to be confirmed
+        let res = run(r#"DELETE INSTANCE OF "Day42";"#);
+        assert_eq!(res["Code"], 0b10101);
+        let res = run(r#"
+            DELETE INSTANCE OF "Day42" WHERE "Day" = 1 COUNT;
+            DELETE INSTANCE OF "Day42" CONFIRM COUNT;
+        "#);
+        assert_eq!(res[0][0]["Count"], 1);
+        assert_eq!(res[1][0]["Count"], 1);
+    }
+
+    #[test]
+    fn bulk_delete_restricted() {
+        let res = run(r#"
+            TEMPLATE "Day42r"; INTEGER "Day"; END "Day42r";
+            TEMPLATE "Note42r"; "Day" REF "Day42r"; END "Note42r";
+            CREATE "mon42r" TEMPLATE "Day42r" WITH SET "Day" VALUE 1;
+            CREATE "tue42r" TEMPLATE "Day42r" WITH SET "Day" VALUE 2;
+            CREATE "n42r" TEMPLATE "Note42r" WITH SET "Day" VALUE "tue42r";
+            DELETE INSTANCE OF "Day42r" CONFIRM;
+        "#);
+        assert_eq!(res["Code"], 0b0111);
+        let res = run(r#"QUERY INSTANCE OF "Day42r";"#);
+        assert_eq!(res[0].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn relative_updates() {
+        let res = run(r#"
+            TEMPLATE "Day43"; INTEGER "Day" MAX 7; FLOAT "Seconds" VALUE 1.5; STRING "Name" VALUE "d"; END "Day43";
+            CREATE "mon43" TEMPLATE "Day43" WITH SET "Day" VALUE 1 SET "Name" APPEND "x";
+            SELECT "mon43";
+            SET "Day" ADD 2;
+            SET "Seconds" MUL 2;
+            SET "Name" APPEND "-mon";
+            SET "Seconds" VALUE "Seconds" + "Day" * 2;
+            END "mon43";
+            QUERY "mon43" FROM INSTANCE;
+        "#);
+        assert_eq!(res[0][0]["Instance"]["data"], serde_json::json!({ "Day": 3, "Seconds": 9.0, "Name": "dx-mon" }));
+
+        let res = run(r#"SELECT "mon43"; SET "Day" ADD 10; END "mon43";"#);
+        assert_eq!(res["Code"], 0b10001);
+        for set in [r#"SET "Day" APPEND "x";"#, r#"SET "Day" MUL 1.5;"#] {
+            let res = run(&format!(r#"SELECT "mon43"; {} END "mon43";"#, set));
+            assert_eq!(res["Code"], 0b0001, "{}", set);
+        }
+    }
+
+    #[test]
+    fn concurrent_increments() {
+        run(r#"
+            TEMPLATE "Counter43"; INTEGER "Hits"; END "Counter43";
+            CREATE "c43" TEMPLATE "Counter43";
+        "#);
+        let threads: Vec<_> = (0..8).map(|_| std::thread::spawn(||
+            run(r#"SELECT "c43"; SET "Hits" ADD 1; END "c43";"#)
+        )).collect();
+        threads.into_iter().for_each(|thread| { thread.join().unwrap(); });
+        let res = run(r#"QUERY "c43" FROM INSTANCE;"#);
+        assert_eq!(res[0][0]["Instance"]["data"]["Hits"], 8);
+    }
+
+    #[test]
+    fn revision_preconditions() {
+        let res = run(r#"
+            TEMPLATE "Day44"; INTEGER "Day"; STRING "Name"; END "Day44";
+            CREATE "mon44" TEMPLATE "Day44";
+            SELECT "mon44" IF REVISION 1; SET "Day" ADD 1; END "mon44";
+            QUERY "mon44" FROM INSTANCE;
+        "#);
+        assert_eq!(res[0][0]["Instance"]["revision"], 2);
+
+        // A stale revision changes nothing
+        let res = run(r#"SELECT "mon44" IF REVISION 1; SET "Day" ADD 1; END "mon44";"#);
+        assert_eq!(res["Code"], 0b10110);
+        let res = run(r#"
+            SELECT "mon44" IF "Day" = 1 AND "Name" = ""; SET "Name" VALUE "x"; END "mon44";
+            SELECT "mon44" IF "Day" = 1 AND "Name" = ""; SET "Name" VALUE "y"; END "mon44";
+        "#);
+        assert_eq!(res["Code"], 0b10110);
+        let res = run(r#"QUERY "mon44" FROM INSTANCE;"#);
+        assert_eq!(res[0][0]["Instance"]["data"], serde_json::json!({ "Day": 1, "Name": "x" }));
+        assert_eq!(res[0][0]["Instance"]["revision"], 3);
+    }
+
+    #[test]
+    fn bulk_update_revisions() {
+        let res = run(r#"
+            TEMPLATE "Day44b"; INTEGER "Day"; END "Day44b";
+            CREATE "mon44b" TEMPLATE "Day44b" WITH SET "Day" VALUE 1;
+            CREATE "tue44b" TEMPLATE "Day44b" WITH SET "Day" VALUE 2;
+            UPDATE INSTANCE OF "Day44b" SET "Day" VALUE 2;
+            QUERY INSTANCE OF "Day44b";
+        "#);
+        // Only the instance, whose value changed, gets a new revision
+        let revisions: Vec<&Value> = res[1].as_array().unwrap().iter().map(|i| &i["Instance"]["revision"]).collect();
+        assert_eq!(revisions, [2, 1]);
+    }
+
+    #[test]
+    fn rename_keeps_references() {
+        let res = run(r#"
+            TEMPLATE "Place45"; STRING "City" UNIQUE; END "Place45";
+            TEMPLATE "Day45"; INTEGER "Day"; END "Day45";
+            TEMPLATE "Weekend45" EXTENDS "Day45"; "Home" TYPE "Place45"; "Prev" REF "Day45"; END "Weekend45";
+            CREATE "mon45" TEMPLATE "Day45";
+            CREATE "sat45" TEMPLATE "Weekend45" WITH SET "Home.City" VALUE "B" SET "Prev" VALUE "mon45";
+            RENAME "mon45" TO "monday45" FROM INSTANCE;
+            RENAME "Day45" TO "D45" FROM TEMPLATE;
+            RENAME "Place45" TO "P45" FROM TEMPLATE;
+            QUERY "sat45" FROM INSTANCE;
+            DESCRIBE "Weekend45";
+        "#);
+        assert_eq!(res[3][0]["Instance"]["data"]["Prev"], "monday45");
+        let description = &res[4][0]["Description"];
+        assert_eq!(description["parent"], "D45");
+        assert_eq!(description["fields"][1]["options"]["template"], "P45");
+        assert_eq!(description["fields"][3]["options"]["reference"]["template"], "D45");
+
+        // The renamed instance is still referenced and its unique value still used
+        let res = run(r#"DELETE "monday45" FROM INSTANCE;"#);
+        assert_eq!(res["Code"], 0b0111);
+        let res = run(r#"CREATE "sun45" TEMPLATE "Weekend45" WITH SET "Home.City" VALUE "B";"#);
+        assert_eq!(res["Code"], 0b10100);
+    }
+
+    #[test]
+    fn rename_conflicts() {
+        let res = run(r#"
+            TEMPLATE "Day45c"; INTEGER "Day"; END "Day45c";
+            CREATE "mon45c" TEMPLATE "Day45c";
+            CREATE "tue45c" TEMPLATE "Day45c";
+            RENAME "tue45c" TO "mon45c" FROM INSTANCE;
+        "#);
+        assert_eq!(res["Code"], 0b0101);
+        let res = run(r#"RENAME "Nope45c" TO "Other45c" FROM TEMPLATE;"#);
+        assert_eq!(res["Code"], 0b1010);
+    }
+
+    #[test]
+    fn search_follows_changes() {
+        let res = run(r#"
+            TEMPLATE "Day47"; STRING "Notes" SEARCHABLE; STRING "Title" VALUE "t" SEARCHABLE; END "Day47";
+            CREATE "mon47" TEMPLATE "Day47" WITH SET "Notes" VALUE "Science lab in the science building";
+            CREATE "tue47" TEMPLATE "Day47" WITH SET "Notes" VALUE "Math and a lab";
+            CREATE "wed47" TEMPLATE "Day47" WITH SET "Notes" VALUE "Sport";
+            SEARCH "Day47" FOR "science lab";
+            SELECT "wed47"; SET "Title" VALUE "Lab day, lab lab"; END "wed47";
+            RENAME "wed47" TO "wednesday47" FROM INSTANCE;
+            DELETE "mon47" FROM INSTANCE;
+            SEARCH "Day47" FOR "lab science";
+            ALTER "Day47"; DROP "Title"; END "Day47";
+            SEARCH "Day47" FOR "lab";
+        "#);
+        let names = |res: &Value| res.as_array().unwrap().iter().map(|i| i["Instance"]["name"].clone()).collect::<Vec<Value>>();
+        assert_eq!(names(&res[0]), ["mon47", "tue47"]);
+        assert_eq!(names(&res[3]), ["wednesday47", "tue47"]);
+        assert_eq!(names(&res[4]), ["tue47"]);
+
+        let res = run(r#"SEARCH "Nope47" FOR "a";"#);
+        assert_eq!(res["Code"], 0b1010);
+    }
+
+    #[test]
+    fn ordered_index_queries() {
+        let res = run(r#"
+            TEMPLATE "Day48"; INTEGER "Day"; STRING "Code" UNIQUE; END "Day48";
+            TEMPLATE "Holiday48" EXTENDS "Day48"; END "Holiday48";
+            CREATE "three48" TEMPLATE "Day48" WITH SET "Day" VALUE 3 SET "Code" VALUE "c";
+            CREATE "one48" TEMPLATE "Day48" WITH SET "Day" VALUE 1 SET "Code" VALUE "a";
+            CREATE "two48" TEMPLATE "Day48" WITH SET "Day" VALUE 9007199254740993 SET "Code" VALUE "b";
+            CREATE "five48" TEMPLATE "Holiday48" WITH SET "Day" VALUE 5;
+            CREATE INDEX ON "Day48" ("Day", "Code");
+            QUERY INSTANCE OF "Day48" WHERE "Day" > 9007199254740992;
+            QUERY INSTANCE OF "Day48" WITH CHILDREN ORDER BY "Day" DESC;
+        "#);
+        let names = |res: &Value| res.as_array().unwrap().iter().map(|i| i["Instance"]["name"].clone()).collect::<Vec<Value>>();
+        assert_eq!(names(&res[0]), ["two48"]);
+        assert_eq!(names(&res[1]), ["two48", "five48", "three48", "one48"]);
+
+        // A failed bulk update leaves the indexes as they were
+        let res = run(r#"UPDATE INSTANCE OF "Day48" SET "Code" VALUE "same";"#);
+        assert_eq!(res["Code"], 0b10100);
+        let res = run(r#"
+            QUERY INSTANCE OF "Day48" WHERE "Code" = "b";
+            RENAME "two48" TO "four48" FROM INSTANCE;
+            DELETE "one48" FROM INSTANCE;
+            UPDATE INSTANCE OF "Day48" SET "Day" VALUE 0 WHERE "Code" = "c";
+            QUERY INSTANCE OF "Day48" ORDER BY "Day";
+        "#);
+        assert_eq!(names(&res[0]), ["two48"]);
+        assert_eq!(names(&res[4]), ["three48", "four48"]);
+
+        let res = run(r#"CREATE INDEX ON "Day48" ("Nope");"#);
+        assert_eq!(res["Code"], 0b0010);
+    }
+
+    #[test]
+    fn large_integers_with_and_without_index() {
+        for (template, index) in [("Big48", ""), ("Big48i", r#"CREATE INDEX ON "Big48i" ("Day");"#)] {
+            let res = run(&format!(r#"
+                TEMPLATE "{0}"; INTEGER "Day"; END "{0}";
+                CREATE "even{0}" TEMPLATE "{0}" WITH SET "Day" VALUE 9007199254740992;
+                CREATE "odd{0}" TEMPLATE "{0}" WITH SET "Day" VALUE 9007199254740993;
+                {1}
+                QUERY INSTANCE OF "{0}" WHERE "Day" > 9007199254740992;
+                QUERY INSTANCE OF "{0}" WHERE "Day" > 9007199254740992.0;
+                QUERY INSTANCE OF "{0}" WHERE "Day" = 9007199254740992.0;
+                QUERY INSTANCE OF "{0}" WHERE "Day" <= 9007199254740993;
+            "#, template, index));
+            let names = |res: &Value| res.as_array().unwrap().iter().map(|i| i["Instance"]["name"].clone()).collect::<Vec<Value>>();
+            let (even, odd) = (format!("even{}", template), format!("odd{}", template));
+            assert_eq!(names(&res[0]), [odd.as_str()], "{}", template);
+            assert_eq!(names(&res[1]), [odd.as_str()], "{}", template);
+            assert_eq!(names(&res[2]), [even.as_str()], "{}", template);
+            assert_eq!(names(&res[3]), [even.as_str(), odd.as_str()], "{}", template);
+        }
+    }
+
+    #[test]
+    fn explain_plans() {
+        let res = run(r#"
+            TEMPLATE "Day49"; INTEGER "Day"; STRING "Title" SEARCHABLE; END "Day49";
+            CREATE "one49" TEMPLATE "Day49" WITH SET "Day" VALUE 1 SET "Title" VALUE "a lab";
+            CREATE "two49" TEMPLATE "Day49" WITH SET "Day" VALUE 2 SET "Title" VALUE "lab";
+            CREATE "three49" TEMPLATE "Day49" WITH SET "Day" VALUE 3;
+            EXPLAIN QUERY INSTANCE OF "Day49" WHERE "Day" >= 2;
+            CREATE INDEX ON "Day49" ("Day");
+            EXPLAIN QUERY INSTANCE OF "Day49" WHERE "Day" >= 2 AND "Title" = "x";
+            EXPLAIN SEARCH "Day49" FOR "lab";
+        "#);
+        let plan = &res[0][0]["Plan"];
+        assert_eq!(plan["access"], serde_json::json!({ "Scan": ["Day49"] }));
+        assert_eq!(plan["rows"], 3);
+        assert_eq!(plan["reads"], serde_json::json!(["INSTANCES", "TEMPLATES", "INDEXES"]));
+        assert_eq!(plan["ast"]["Statement"]["variant"]["Token"][0], "Query");
+        let plan = &res[1][0]["Plan"];
+        assert_eq!(plan["access"], serde_json::json!({ "Index": { "template": "Day49", "fields": ["Day"] } }));
+        assert_eq!(plan["rows"], 2);
+        let plan = &res[2][0]["Plan"];
+        assert_eq!(plan["rows"], 2);
+        assert_eq!(plan["reads"], serde_json::json!(["INSTANCES", "TEMPLATES", "SEARCH"]));
+    }
+
+    #[test]
+    fn explain_changes_nothing() {
+        let res = run(r#"
+            TEMPLATE "Day49c"; INTEGER "Day"; END "Day49c";
+            CREATE "one49c" TEMPLATE "Day49c" WITH SET "Day" VALUE 1;
+            EXPLAIN SELECT "one49c"; SET "Day" VALUE 9; END "one49c";
+            EXPLAIN UPDATE INSTANCE OF "Day49c" SET "Day" ADD 1;
+            EXPLAIN DELETE "Day49c" FROM TEMPLATE;
+            QUERY "one49c" FROM INSTANCE;
+        "#);
+        assert_eq!(res[0][0]["Plan"]["stores"], serde_json::json!(["INSTANCES", "UNIQUE", "SEARCH", "INDEXES"]));
+        assert_eq!(res[1][0]["Plan"]["rows"], 1);
+        assert_eq!(res[2][0]["Plan"]["stores"], serde_json::json!(["TEMPLATES", "HISTORY", "INSTANCES", "UNIQUE", "SEARCH", "INDEXES"]));
+        assert_eq!(res[3][0]["Instance"]["data"]["Day"], 1);
+        assert_eq!(res[3][0]["Instance"]["revision"], 1);
+
+        let res = run(r#"EXPLAIN QUERY INSTANCE OF "Nope49c";"#);
+        assert_eq!(res["Code"], 0b1010);
+    }
+
+    #[test]
+    fn bound_parameters() {
+        run(r#"TEMPLATE "Day50"; INTEGER "Day"; STRING "Title"; END "Day50";"#);
+        let source = r#"CREATE $1 TEMPLATE "Day50" WITH SET "Title" VALUE $2 SET "Day" VALUE $3; QUERY INSTANCE OF "Day50" WHERE "Day" < 0;"#;
+        let params = serde_json::json!(["x50\"; DELETE \"Day50\" FROM TEMPLATE;", "OR 1=1", -5]);
+        let res: Value = serde_json::from_str(&query::data(lexer::parse(source.to_string()), &params)).unwrap();
+        let instance = &res[0][0]["Instance"];
+        assert_eq!(instance["name"], "x50\"; DELETE \"Day50\" FROM TEMPLATE;");
+        assert_eq!(instance["data"], serde_json::json!({ "Day": -5, "Title": "OR 1=1" }));
+
+        let res: Value = serde_json::from_str(&query::data(lexer::parse(r#"QUERY INSTANCE OF "Day50" WHERE "Title" = $2;"#.to_string()), &serde_json::json!(["x"]))).unwrap();
+        assert_eq!(res["Code"], 0b11100);
+    }
+}
@@ -0,0 +1,209 @@
+use serde::Serialize;
+
+use crate::{ast::Node, error::PangError, lexer::data::Token};
+
+use super::{backend::{self, Access}, instance_filter, prop::create_condition};
+
+/// Static stores a statement reads or changes
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum Store {
+    INSTANCES,
+    TEMPLATES,
+    HISTORY,
+    UNIQUE,
+    SEARCH,
+    INDEXES,
+}
+
+/// Stores changed by writing instances
+const INSTANCE_STORES: [Store; 4] = [Store::INSTANCES, Store::UNIQUE, Store::SEARCH, Store::INDEXES];
+
+/// How a statement would be executed
+#[derive(Serialize, Debug)]
+pub struct Plan {
+    /// The parsed statement
+    pub ast: Node,
+    pub access: Access,
+    /// Estimated number of objects the statement returns or changes
+    pub rows: usize,
+    /// Stores a query would read
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub reads: Vec<Store>,
+    /// Stores a mutation would change
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub stores: Vec<Store>,
+}
+
+/// Plans a statement without executing it
+pub fn explain(node: Node) -> Result<Plan, PangError> {
+    let (access, rows, stores) = match &node {
+        Node::Statement { variant, context, child } => match **variant {
+            Node::Token(token, loc) => {
+                let nodes = child.clone().map(|child| child.flatten()).unwrap_or_default();
+                explain_statement(token, context, nodes, loc)?
+            },
+            _ => return Err(PangError::ExecutionError),
+        },
+        Node::Shell { outside, inside } => explain_shell(outside, inside)?,
+        _ => return Err(PangError::ExecutionError),
+    };
+    // Statements, that change nothing, are queries
+    let reads = match stores.is_empty() {
+        true => reads(&node),
+        false => Vec::new(),
+    };
+    Ok(Plan { ast: node, access, rows, reads, stores })
+}
+
+/// Stores a query reads
+fn reads(node: &Node) -> Vec<Store> {
+    let (token, context, nodes) = match node {
+        Node::Statement { variant, context, child } => match **variant {
+            Node::Token(token, _) => (token, &**context, child.clone().map(|child| child.flatten()).unwrap_or_default()),
+            _ => return Vec::new(),
+        },
+        Node::Shell { .. } => return vec![Store::INSTANCES, Store::TEMPLATES],
+        _ => return Vec::new(),
+    };
+    match (token, context) {
+        (Token::Query, Node::Token(Token::Instance, _)) => vec![Store::INSTANCES, Store::TEMPLATES, Store::INDEXES],
+        (Token::Query, Node::Token(Token::Template, _)) => vec![Store::TEMPLATES],
+        (Token::Query, Node::Literal(_, _)) => match nodes.as_slice() {
+            [_, Node::Token(Token::Template, _), Node::Token(Token::History, _)] => vec![Store::TEMPLATES, Store::HISTORY],
+            [_, Node::Token(Token::Template, _), ..] => vec![Store::TEMPLATES],
+            _ => vec![Store::INSTANCES],
+        },
+        (Token::Search, _) => vec![Store::INSTANCES, Store::TEMPLATES, Store::SEARCH],
+        (Token::Describe, _) => vec![Store::INSTANCES, Store::TEMPLATES],
+        _ => Vec::new(),
+    }
+}
+
+/// Plans a single line statement
+fn explain_statement(token: Token, context: &Node, mut nodes: Vec<Node>, loc: usize) -> Result<(Access, usize, Vec<Store>), PangError> {
+    Ok(match (token, context) {
+        (Token::Query, Node::Token(Token::Instance, _)) => {
+            if let [Node::Token(Token::Expand, _)] = nodes.as_slice() {
+                return Ok((Access::Scan(Vec::new()), backend::count_instances(None), Vec::new()))
+            }
+            let (template, condition, order) = instance_filter(nodes)?;
+            let (access, rows) = backend::plan_instances(template, condition.as_ref(), order.as_ref().map(|(field, _)| field), loc)?;
+            (access, rows, Vec::new())
+        },
+        (Token::Query, Node::Token(Token::Template, _)) => (Access::Scan(Vec::new()), backend::count_templates(None), Vec::new()),
+        (Token::Query, Node::Literal(name, _)) => {
+            let rows = match nodes.as_slice() {
+                [_, Node::Token(Token::Template, _), Node::Token(Token::History, _)] => backend::count_template_versions(name),
+                [_, Node::Token(Token::Template, _), ..] => backend::count_templates(Some(name)),
+                _ => backend::count_instances(Some(name)),
+            };
+            (Access::Name(name.clone()), rows, Vec::new())
+        },
+        (Token::Search, Node::Literal(name, _)) => {
+            let template = backend::copy_template(name.clone(), loc)?;
+            let fields = template.options.iter().filter(|(_, options)| options.searchable).map(|(field, _)| field.clone()).collect();
+            let rows = match nodes.as_slice() {
+                [_, Node::Literal(query, _)] => backend::estimate_search(name.clone(), query.clone(), loc)?,
+                _ => return Err(PangError::ExecutionError),
+            };
+            (Access::Index { template: name.clone(), fields }, rows, Vec::new())
+        },
+        (Token::Describe, Node::Literal(name, _)) => (Access::Name(name.clone()), backend::count_templates(Some(name)), Vec::new()),
+        // CREATE INDEX and DROP INDEX
+        (Token::Create | Token::Drop, Node::Token(Token::Index, _)) => match nodes.as_slice() {
+            [_, Node::Literal(name, _), _] => (Access::Name(name.clone()), instances_of(name, false, loc), vec![Store::TEMPLATES, Store::INDEXES]),
+            _ => return Err(PangError::ExecutionError),
+        },
+        (Token::Create, Node::Literal(_, _)) => match nodes.as_slice() {
+            [_, Node::Literal(origin, _), ..] => (Access::Name(origin.clone()), 1, INSTANCE_STORES.to_vec()),
+            _ => return Err(PangError::ExecutionError),
+        },
+        (Token::Upsert, Node::Literal(name, _)) => (Access::Name(name.clone()), 1, INSTANCE_STORES.to_vec()),
+        // UPDATE INSTANCE OF "template" SET ... WHERE ...
+        (Token::Update, Node::Token(Token::Instance, _)) => {
+            let name = match nodes.get(1) {
+                Some(Node::Literal(name, _)) => name.clone(),
+                _ => return Err(PangError::ExecutionError),
+            };
+            let condition = match nodes.iter().position(|node| matches!(node, Node::Token(Token::Where, _))) {
+                Some(position) => Some(create_condition(nodes.split_off(position + 1))?),
+                None => None,
+            };
+            let (access, rows) = backend::plan_instances(Some((name, false)), condition.as_ref(), None, loc)?;
+            (access, rows, INSTANCE_STORES.to_vec())
+        },
+        // DELETE INSTANCE OF "template" WHERE ... COUNT
+        (Token::Delete, Node::Token(Token::Instance, _)) => {
+            if matches!(nodes.last(), Some(Node::Token(Token::Count, _))) {
+                nodes.pop();
+            }
+            let name = match nodes.get(1) {
+                Some(Node::Literal(name, _)) => name.clone(),
+                _ => return Err(PangError::ExecutionError),
+            };
+            let condition = match nodes.get(2) {
+                Some(Node::Token(Token::Where, _)) => Some(create_condition(nodes.split_off(3))?),
+                _ => None,
+            };
+            let (access, rows) = backend::plan_instances(Some((name, false)), condition.as_ref(), None, loc)?;
+            (access, rows, INSTANCE_STORES.to_vec())
+        },
+        (Token::Delete, Node::Literal(name, _)) => match nodes.get(1) {
+            // The instances of a deleted template are deleted as well
+            Some(Node::Token(Token::Template, _)) => {
+                let mut stores = vec![Store::TEMPLATES, Store::HISTORY];
+                stores.extend(INSTANCE_STORES);
+                (Access::Name(name.clone()), instances_of(name, false, loc), stores)
+            },
+            _ => (Access::Name(name.clone()), backend::count_instances(Some(name)), INSTANCE_STORES.to_vec()),
+        },
+        (Token::Rename, Node::Literal(name, _)) => match nodes.last() {
+            Some(Node::Token(Token::Template, _)) => {
+                let mut stores = vec![Store::TEMPLATES, Store::HISTORY];
+                stores.extend(INSTANCE_STORES);
+                (Access::Name(name.clone()), backend::count_templates(Some(name)), stores)
+            },
+            _ => (Access::Name(name.clone()), backend::count_instances(Some(name)), INSTANCE_STORES.to_vec()),
+        },
+        _ => return Err(PangError::ExecutionError),
+    })
+}
+
+/// Plans a statement spanning multiple lines
+fn explain_shell(outside: &Node, inside: &[Box<Node>]) -> Result<(Access, usize, Vec<Store>), PangError> {
+    let (token, name, nodes, loc) = match outside {
+        Node::Statement { variant, context, child } => match (&**variant, &**context) {
+            (Node::Token(token, loc), Node::Literal(name, _)) =>
+                (*token, name, child.clone().map(|child| child.flatten()).unwrap_or_default(), *loc),
+            _ => return Err(PangError::ExecutionError),
+        },
+        _ => return Err(PangError::ExecutionError),
+    };
+    Ok(match token {
+        Token::Select => {
+            // Only selections, that set a field, change the instance
+            let sets = inside.iter().any(|node| matches!(node.clone().flatten().first(), Some(Node::Token(Token::Set, _))));
+            (Access::Name(name.clone()), backend::count_instances(Some(name)), if sets { INSTANCE_STORES.to_vec() } else { Vec::new() })
+        },
+        Token::Template => (Access::Name(name.clone()), 1, vec![Store::TEMPLATES]),
+        // The instances of the altered template and its children are migrated
+        Token::Alter => {
+            let stores = match nodes.first() {
+                Some(Node::Token(Token::Dry, _)) => Vec::new(),
+                _ => {
+                    let mut stores = vec![Store::TEMPLATES, Store::HISTORY];
+                    stores.extend(INSTANCE_STORES);
+                    stores
+                },
+            };
+            (Access::Name(name.clone()), instances_of(name, true, loc), stores)
+        },
+        _ => return Err(PangError::ExecutionError),
+    })
+}
+
+/// Number of instances of a template, optionally including those of its children
+fn instances_of(name: &str, children: bool, loc: usize) -> usize {
+    backend::plan_instances(Some((name.to_string(), children)), None, None, loc).map_or(0, |(_, rows)| rows)
+}
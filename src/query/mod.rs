@@ -1,16 +1,43 @@
-use crate::{data::{structure::{Template, Instance}}, ast::{self, Node}, error::PangError};
+use crate::{data::{structure::{Template, Instance, FieldOptions, Description}, serialization::{Data, DataType}, condition::Condition}, ast::{self, Node}, error::PangError};
+use linked_hash_map::LinkedHashMap;
 use serde::{Serialize, Deserialize};
-use crate::lexer::data::{Token, TokenMatch};
+use crate::lexer::{self, data::{Token, TokenMatch}};
 
-use self::{backend::{push_template, remove_instance, push_instance}, prop::{create_template_prop, create_select_prop}};
+use self::{backend::{push_template, Alteration, Origin}, explain::{explain, Plan}, prop::{create_template_prop, create_select_prop, create_get_prop, create_condition}};
 
 pub mod backend;
 mod prop;
+mod explain;
 
 #[derive(Serialize, Deserialize)]
 enum QueryResult {
     Template(Template),
     Instance(Instance),
+    Data(LinkedHashMap<String, Data>),
+    Description(Description),
+    /// An [`Instance`] with its references replaced by the referenced instances
+    #[serde(rename(serialize = "Instance"))]
+    Expanded(serde_json::Value),
+    /// An [`Instance`], that a dry run couldn't migrate
+    #[serde(skip_deserializing)]
+    Failure { instance: String, error: PangError },
+    /// What a conditional statement did to the named object
+    Action { action: Action, name: String },
+    /// Number of instances affected by a bulk statement
+    Count(usize),
+    /// How an `EXPLAIN` statement would be executed
+    #[serde(skip_deserializing)]
+    Plan(Plan),
+}
+
+/// Action taken by a conditional `CREATE`, `DELETE` or `TEMPLATE` or an `UPSERT`
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Serialize, Deserialize)]
+enum Action {
+    CREATED,
+    UPDATED,
+    DELETED,
+    SKIPPED,
 }
 
 impl From<Instance> for QueryResult {
@@ -49,19 +76,34 @@ fn exec_branch(branch: Node) -> Result<Option<Vec<QueryResult>>, PangError> {
             match *variant {
                 Node::Token(token, loc) => match token {
                     Token::Query => return Ok(Some(query_statement(*context, child, loc)?)),
-                    Token::Create => { 
-                        create_statement(*context, child, loc)?; 
-                        Ok(None)
-                    },
+                    Token::Explain => Ok(Some(vec![QueryResult::Plan(explain(*context)?)])),
+                    Token::Create => Ok(create_statement(*context, child, loc)?.map(|action| vec![action])),
+                    Token::Upsert => Ok(Some(vec![upsert_statement(*context, child, loc)?])),
+                    Token::Update => Ok(Some(vec![update_statement(*context, child, loc)?])),
                     Token::Delete => return Ok(Some(delete_statement(*context, child, loc)?)),
+                    Token::Rename => Ok(Some(vec![rename_statement(*context, child, loc)?])),
+                    Token::Search => match (*context, child.map(|child| child.flatten()).as_deref()) {
+                        (Node::Literal(name, loc), Some([Node::Token(Token::For, _), Node::Literal(query, _)])) =>
+                            Ok(Some(backend::search_instances(name, query.clone(), loc)?.into_iter().map(QueryResult::from).collect())),
+                        _ => Err(PangError::ExecutionError),
+                    },
+                    Token::Drop => match *context {
+                        Node::Token(Token::Index, _) => index_statement(child, false, loc).map(|_| None),
+                        _ => Err(PangError::ExecutionError),
+                    },
+                    Token::Describe => match *context {
+                        Node::Literal(name, loc) => Ok(Some(vec![QueryResult::Description(backend::describe_template(name, loc)?)])),
+                        _ => Err(PangError::ExecutionError),
+                    },
                     _ => return Err(PangError::ExecutionError),
                 },
                 _ => return Err(PangError::ExecutionError),
             }
         },
         Node::Shell { outside, inside } => {
-            let (token, name, loc) = match *outside {
-                Node::Statement { variant, context, child: _} => {
+            let inside: Vec<Node> = inside.into_iter().map(|node| *node).collect();
+            let (token, name, child, loc) = match *outside {
+                Node::Statement { variant, context, child } => {
                     // Token
                     let (token, loc) = match *variant {
                         Node::Token(token, loc) => (token, loc),
@@ -72,19 +114,41 @@ fn exec_branch(branch: Node) -> Result<Option<Vec<QueryResult>>, PangError> {
                         Node::Literal(name, _) => name,
                         _ => return Err(PangError::ExecutionError),
                     };
-                    (token, name, loc)
+                    (token, name, child, loc)
                 },
                 _ => return Err(PangError::ExecutionError),
             };
             
             match token {
                 Token::Select => {
-                    make_selection(name, inside, loc)?;
-                    Ok(None)
+                    let res = make_selection(name, child, inside, loc)?;
+                    Ok(if res.is_empty() { None } else { Some(res) })
                 },
                 Token::Template => { 
-                    create_template(name, inside, loc)?;
-                    Ok(None)
+                    let nodes = child.map(|child| child.flatten()).unwrap_or_default();
+                    let parent = match nodes.as_slice() {
+                        [Node::Token(Token::Extends, _), Node::Literal(parent, _), ..] => Some(parent.clone()),
+                        _ => None,
+                    };
+                    // IF NOT EXISTS
+                    if !matches!(nodes.last(), Some(Node::Token(Token::Exists, _))) {
+                        create_template(name, parent, inside, loc)?;
+                        return Ok(None)
+                    }
+                    let action = match backend::copy_template(name.clone(), loc) {
+                        Ok(_) => Action::SKIPPED,
+                        Err(_) => match create_template(name.clone(), parent, inside, loc) {
+                            Ok(()) => Action::CREATED,
+                            Err(PangError::TemplateAlreadyExists(_, _)) => Action::SKIPPED,
+                            Err(e) => return Err(e),
+                        },
+                    };
+                    Ok(Some(vec![QueryResult::Action { action, name }]))
+                },
+                Token::Alter => {
+                    let dry = child.is_some_and(|child| matches!(child.flatten().first(), Some(Node::Token(Token::Dry, _))));
+                    let res = alter_template(name, inside, dry, loc)?;
+                    Ok(if dry { Some(res) } else { None })
                 },
                 _ => return Err(PangError::ExecutionError),
             }
@@ -93,35 +157,133 @@ fn exec_branch(branch: Node) -> Result<Option<Vec<QueryResult>>, PangError> {
     }
 }
 
-/// Creates a template from a branch
-fn create_template(name: String, properties: Vec<Box<Node>>, loc: usize) -> Result<(), PangError> {
-    let mut template = Template::new(name);
+/// Creates a template from a branch, inheriting the fields of the parent template
+fn create_template(name: String, parent: Option<String>, properties: Vec<Node>, loc: usize) -> Result<(), PangError> {
+    let mut template = Template::new(name.clone());
+    let inherited = match parent {
+        Some(parent) => template.extend(&backend::copy_template(parent, loc)?).build().data,
+        None => LinkedHashMap::new(),
+    };
     for prop in properties {
-        let (name, data) = create_template_prop(*prop)?;
+        let (name, data, options) = create_template_prop(&name, prop)?;
+        if inherited.contains_key(&name) {
+            return Err(PangError::DataAlreadyExists(name, loc))
+        }
+        for (name, options) in field_options(name.clone(), options, loc)? {
+            template.add_options(name, options);
+        }
         template.add_data(name, data);
     }
-    push_template(template.build(), loc)?;
+    let mut template = template.build();
+    let mut data = template.data.clone();
+    template.compute(&mut data, loc)?;
+    template.data = data;
+    push_template(template, loc)?;
     Ok(())
 }
 
-/// Makes the instances selection and overwrites the values
-fn make_selection(name: String, properties: Vec<Box<Node>>, loc: usize) -> Result<(), PangError> {
-    let mut instance = remove_instance(name, loc)?;
+/// Options of a field, options of nested fields are kept under their dotted path
+fn field_options(name: String, options: FieldOptions, loc: usize) -> Result<Vec<(String, FieldOptions)>, PangError> {
+    let mut res = Vec::new();
+    if let Some(nested) = &options.template {
+        for (field, nested_options) in backend::copy_template(nested.clone(), loc)?.options {
+            res.push((format!("{}.{}", name, field), nested_options));
+        }
+    }
+    res.push((name, options));
+    Ok(res)
+}
+
+/// Alters the fields of a template from a branch
+fn alter_template(name: String, properties: Vec<Node>, dry: bool, loc: usize) -> Result<Vec<QueryResult>, PangError> {
+    let mut alterations = Vec::new();
     for prop in properties {
-        let (name, (data, loc)) = create_select_prop(*prop)?;
-        instance.overwrite(name, data, loc)?;
+        let mut nodes = prop.flatten();
+        let token = match nodes.remove(0) {
+            Node::Token(token, _) => token,
+            _ => return Err(PangError::ExecutionError),
+        };
+        let alteration = match (token, nodes.as_slice()) {
+            (Token::Add, _) => {
+                let prop = Node::unflatten(nodes).ok_or(PangError::ExecutionError)?;
+                let (field, data, options) = create_template_prop(&name, prop)?;
+                Alteration::Add(field.clone(), data, field_options(field, options, loc)?)
+            },
+            (Token::Drop, [Node::Literal(field, _)]) => Alteration::Drop(field.clone()),
+            (Token::Rename, [Node::Literal(from, _), Node::Token(Token::To, _), Node::Literal(to, _)]) => 
+                Alteration::Rename(from.clone(), to.clone()),
+            (Token::Change, [Node::Literal(field, _), Node::Token(Token::To, _), Node::Token(data_type, _)]) => {
+                let data_type = match data_type {
+                    Token::StringType => DataType::STRING,
+                    Token::IntegerType => DataType::INTEGER,
+                    Token::FloatType => DataType::FLOAT,
+                    _ => return Err(PangError::ExecutionError),
+                };
+                Alteration::Change(field.clone(), data_type)
+            },
+            _ => return Err(PangError::ExecutionError),
+        };
+        alterations.push(alteration);
     }
-    push_instance(instance, loc)?;
-    Ok(())
+    let failures = backend::alter_template(name, alterations, dry, loc)?;
+    Ok(failures.into_iter().map(|(instance, error)| QueryResult::Failure { instance, error }).collect())
+}
+
+/// Makes the instances selection, overwrites the values and returns the requested ones
+fn make_selection(name: String, child: Option<Box<Node>>, properties: Vec<Node>, loc: usize) -> Result<Vec<QueryResult>, PangError> {
+    // IF REVISION 7 or IF "field" = value
+    let mut nodes = child.map(|child| child.flatten()).unwrap_or_default();
+    let (revision, condition) = match nodes.as_slice() {
+        [] => (None, None),
+        [Node::Token(Token::If, _), Node::Token(Token::Revision, _), Node::Int(revision, _)] => (Some(*revision as u64), None),
+        [Node::Token(Token::If, _), ..] => (None, Some(create_condition(nodes.split_off(1))?)),
+        _ => return Err(PangError::ExecutionError),
+    };
+    // The whole selection is applied under the lock, so values can be updated based on the current ones
+    backend::modify_instance(name.clone(), loc, |instance, template| {
+        let unchanged = match (revision, &condition) {
+            (Some(revision), _) => instance.revision == revision,
+            (_, Some(condition)) => condition.matches(instance, loc)?,
+            _ => true,
+        };
+        if !unchanged {
+            return Err(PangError::PreconditionFailed(name, loc))
+        }
+        let mut res = Vec::new();
+        for prop in properties {
+            let get = match &prop {
+                Node::Statement { variant, context: _, child: _ } => matches!(**variant, Node::Token(Token::Get, _)),
+                _ => false,
+            };
+            if get {
+                let (name, loc) = create_get_prop(prop)?;
+                let data = instance.get(&name, loc)?;
+                let mut map = LinkedHashMap::new();
+                map.insert(name, data);
+                res.push(QueryResult::Data(map));
+            } else {
+                let (name, (expression, loc)) = create_select_prop(template, prop)?;
+                instance.assign(template, name, &expression, loc)?;
+                template.compute(&mut instance.data, loc)?;
+            }
+        }
+        Ok(res)
+    })
 }
 
 /// Queries the data from the backend
-fn query_statement(context: Node, child: Option<Box<Node>>, _loc: usize) -> Result<Vec<QueryResult>, PangError> {
+fn query_statement(context: Node, child: Option<Box<Node>>, loc: usize) -> Result<Vec<QueryResult>, PangError> {
     let name = match context {
         Node::Literal(name, _) => Ok(name),
         Node::Token(token, _) => match token {
             Token::Instance => {
-                return Ok(backend::copy_instances().iter().map(|e| e.clone().into()).collect())
+                // EXPAND or OF "template" WITH CHILDREN WHERE ... ORDER BY "field" DESC
+                let nodes = child.map(|child| child.flatten()).unwrap_or_default();
+                if let [Node::Token(Token::Expand, _)] = nodes.as_slice() {
+                    return Ok(backend::expand_references(backend::copy_instances()).into_iter().map(QueryResult::Expanded).collect())
+                }
+                let (template, condition, order) = instance_filter(nodes)?;
+                return Ok(backend::find_instances(template, condition, order, loc)?.into_iter().map(|e| e.into()).collect())
             },
             Token::Template => {
                 return Ok(backend::copy_templates().iter().map(|e| e.clone().into()).collect())
@@ -132,14 +294,24 @@ fn query_statement(context: Node, child: Option<Box<Node>>, _loc: usize) -> Resu
     }?;
     match child {
         Some(child) => match *child {
-            Node::Statement { variant: _, context, child: _ } => {
+            Node::Statement { variant: _, context, child } => {
                 match *context {
                     Node::Token(token, loc) => match token {
-                        Token::Template => {
-                            Ok(vec![backend::copy_template(name, loc)?.into()])
+                        Token::Template => match child.map(|child| child.flatten()).as_deref() {
+                            Some([Node::Token(Token::Version, _), Node::Int(version, loc)]) => {
+                                Ok(vec![backend::copy_template_version(name, *version as usize, *loc)?.into()])
+                            },
+                            Some([Node::Token(Token::History, _)]) => {
+                                Ok(backend::copy_template_history(name, loc)?.into_iter().map(|e| e.into()).collect())
+                            },
+                            _ => Ok(vec![backend::copy_template(name, loc)?.into()]),
                         }
                         Token::Instance => {
-                            Ok(vec![backend::copy_instance(name, loc)?.into()])
+                            let instances = vec![backend::copy_instance(name, loc)?];
+                            Ok(match child {
+                                Some(_) => backend::expand_references(instances).into_iter().map(QueryResult::Expanded).collect(),
+                                None => instances.into_iter().map(|e| e.into()).collect(),
+                            })
                         }
                         _ => Err(PangError::ExecutionError),
                     }
@@ -152,67 +324,181 @@ fn query_statement(context: Node, child: Option<Box<Node>>, _loc: usize) -> Resu
     }
 }
 
+/// Template with its children, condition and order with its direction of a `QUERY INSTANCE` statement
+type InstanceFilter = (Option<(String, bool)>, Option<Condition>, Option<(String, bool)>);
+
+/// Splits the nodes of a `QUERY INSTANCE` statement into the template, the condition and the order
+fn instance_filter(mut nodes: Vec<Node>) -> Result<InstanceFilter, PangError> {
+    // OF "template" WITH CHILDREN WHERE ... ORDER BY "field" DESC
+    let order = match nodes.iter().position(|node| matches!(node, Node::Token(Token::Order, _))) {
+        Some(position) => match nodes.split_off(position).as_slice() {
+            [_, _, Node::Literal(field, _), rest @ ..] =>
+                Some((field.clone(), matches!(rest, [Node::Token(Token::Desc, _)]))),
+            _ => return Err(PangError::ExecutionError),
+        },
+        None => None,
+    };
+    let template = match nodes.as_slice() {
+        [Node::Token(Token::Of, _), Node::Literal(template, _), rest @ ..] => {
+            let children = matches!(rest, [Node::Token(Token::With, _), Node::Token(Token::Children, _), ..]);
+            let template = Some((template.clone(), children));
+            nodes.drain(..if children { 4 } else { 2 });
+            template
+        },
+        _ => None,
+    };
+    if matches!(nodes.first(), Some(Node::Token(Token::Where, _))) {
+        nodes.remove(0);
+    }
+    let condition = match nodes.is_empty() {
+        true => None,
+        false => Some(create_condition(nodes)?),
+    };
+    Ok((template, condition, order))
+}
+
 /// Makes a Instance or Template entry in the backend
-fn create_statement(context: Node, child: Option<Box<Node>>, loc: usize) -> Result<(), PangError> {
+fn create_statement(context: Node, child: Option<Box<Node>>, loc: usize) -> Result<Option<QueryResult>, PangError> {
     let name = match context {
         Node::Literal(name, _) => Ok(name),
+        Node::Token(Token::Index, _) => return index_statement(child, true, loc).map(|_| None),
         _ => Err(PangError::ExecutionError),
     }?;
-    match child {
-        Some(child) => match *child {
-            Node::Statement { variant, context, child: _ } => {
-                let context = match *context {
-                    Node::Literal(context, _) => Ok(context),
-                    _ => Err(PangError::ExecutionError),
-                }?;
-                match *variant {
-                    Node::Token(token, _) => match token {
-                        Token::Template => {
-                            let template = backend::copy_template(context, loc)?;
-                            let instance = Instance::new(name, template);
-                            backend::push_instance(instance, loc)?;
-                            Ok(())
-                        },
-                        Token::Instance => {
-                            let origin = backend::copy_instance(context, loc)?;
-                            let mut instance = Instance::new(name, origin.template);
-                            instance.data = origin.data;
-                            backend::push_instance(instance, loc)?;
-                            Ok(())
-                        },
-                        _ => Err(PangError::ExecutionError),
-                    },
-                    _ => Err(PangError::ExecutionError),
-                }?;
-                Ok(())
-            },
-            _ => Err(PangError::ExecutionError),
-        },
-        None => Err(PangError::ExecutionError),
+    // <TEMPLATE|INSTANCE> "origin" IF NOT EXISTS WITH SET ...
+    let nodes = child.map(|child| child.flatten()).unwrap_or_default();
+    let (token, origin, mut rest) = match nodes.as_slice() {
+        [Node::Token(token, _), Node::Literal(origin, _), rest @ ..] => (*token, origin.clone(), rest),
+        _ => return Err(PangError::ExecutionError),
+    };
+    let conditional = matches!(rest, [Node::Token(Token::If, _), ..]);
+    if conditional {
+        rest = &rest[3..];
+    }
+    let origin = match token {
+        Token::Template => Origin::Template(origin),
+        Token::Instance => Origin::Instance(origin),
+        _ => return Err(PangError::ExecutionError),
+    };
+    match backend::create_instance(name.clone(), origin, loc, |instance, template| apply_sets(instance, template, rest)) {
+        Ok(()) if conditional => Ok(Some(QueryResult::Action { action: Action::CREATED, name })),
+        Ok(()) => Ok(None),
+        Err(PangError::InstanceAlreadyExists(_, _)) if conditional => Ok(Some(QueryResult::Action { action: Action::SKIPPED, name })),
+        Err(e) => Err(e),
+    }
+}
+
+/// Creates an instance of a template or updates it, if it already exists
+fn upsert_statement(context: Node, child: Option<Box<Node>>, loc: usize) -> Result<QueryResult, PangError> {
+    let name = match context {
+        Node::Literal(name, _) => Ok(name),
+        _ => Err(PangError::ExecutionError),
     }?;
+    // TEMPLATE "template" WITH SET ...
+    let nodes = child.map(|child| child.flatten()).unwrap_or_default();
+    let (template, rest) = match nodes.as_slice() {
+        [Node::Token(Token::Template, _), Node::Literal(template, _), rest @ ..] => (template.clone(), rest),
+        _ => return Err(PangError::ExecutionError),
+    };
+    let action = match backend::upsert_instance(name.clone(), template, loc, |instance, template| apply_sets(instance, template, rest))? {
+        true => Action::CREATED,
+        false => Action::UPDATED,
+    };
+    Ok(QueryResult::Action { action, name })
+}
+
+/// Overwrites fields of every instance of a template, that matches the `WHERE` condition
+fn update_statement(context: Node, child: Option<Box<Node>>, loc: usize) -> Result<QueryResult, PangError> {
+    match context {
+        Node::Token(Token::Instance, _) => {},
+        _ => return Err(PangError::ExecutionError),
+    };
+    // OF "template" SET ... WHERE ...
+    let mut nodes = child.map(|child| child.flatten()).unwrap_or_default();
+    let template = match nodes.get(..2) {
+        Some([Node::Token(Token::Of, _), Node::Literal(template, loc)]) => backend::copy_template(template.clone(), *loc)?,
+        _ => return Err(PangError::ExecutionError),
+    };
+    let condition = match nodes.iter().position(|node| matches!(node, Node::Token(Token::Where, _))) {
+        Some(pos) => Some(create_condition(nodes.split_off(pos + 1))?),
+        None => None,
+    };
+    let end = nodes.len() - condition.is_some() as usize;
+    let mut sets = Vec::new();
+    for prop in split_sets(&nodes[2..end])? {
+        let (field, (expression, loc)) = create_select_prop(&template, prop)?;
+        sets.push((field, expression, loc));
+    }
+    Ok(QueryResult::Count(backend::update_instances(template.name, sets, condition, loc)?))
+}
+
+/// Applies `WITH SET ... VALUE ...` to an instance
+fn apply_sets(instance: &mut Instance, template: &Template, nodes: &[Node]) -> Result<(), PangError> {
+    for prop in split_sets(nodes.get(1..).unwrap_or_default())? {
+        let (field, (expression, loc)) = create_select_prop(template, prop)?;
+        instance.assign(template, field, &expression, loc)?;
+        template.compute(&mut instance.data, loc)?;
+    }
     Ok(())
 }
 
+/// Splits a sequence of `SET` statements
+fn split_sets(nodes: &[Node]) -> Result<Vec<Node>, PangError> {
+    let mut sets: Vec<Vec<Node>> = Vec::new();
+    for node in nodes {
+        match (node, sets.last_mut()) {
+            (Node::Token(Token::Set, _), _) => sets.push(vec![node.clone()]),
+            (_, Some(set)) => set.push(node.clone()),
+            (_, None) => return Err(PangError::ExecutionError),
+        }
+    }
+    sets.into_iter().map(|set| Node::unflatten(set).ok_or(PangError::ExecutionError)).collect()
+}
+
+/// Renames an instance or template in the backend
+fn rename_statement(context: Node, child: Option<Box<Node>>, loc: usize) -> Result<QueryResult, PangError> {
+    let from = match context {
+        Node::Literal(name, _) => name,
+        _ => return Err(PangError::ExecutionError),
+    };
+    // TO "name" FROM <TEMPLATE|INSTANCE>
+    match child.map(|child| child.flatten()).as_deref() {
+        Some([Node::Token(Token::To, _), Node::Literal(to, _), Node::Token(Token::From, _), Node::Token(token, _)]) => match token {
+            Token::Template => backend::rename_template(from, to.clone(), loc).map(QueryResult::from),
+            Token::Instance => backend::rename_instance(from, to.clone(), loc).map(QueryResult::from),
+            _ => Err(PangError::ExecutionError),
+        },
+        _ => Err(PangError::ExecutionError),
+    }
+}
+
 /// Deletes data from the backend
 fn delete_statement(context: Node, child: Option<Box<Node>>, loc: usize) -> Result<Vec<QueryResult>, PangError> {
     let name = match context {
         Node::Literal(name, _) => Ok(name),
+        Node::Token(Token::Instance, _) => return delete_instances(child, loc),
         _ => Err(PangError::ExecutionError),
     }?;
     Ok(vec![match child {
         Some(child) => match *child {
-            Node::Statement { variant: _, context, child: _ } => {
-                match *context {
+            Node::Statement { variant: _, context, child } => {
+                let res = match *context {
                     Node::Token(token, _) => match token {
                         Token::Template => {
-                            Ok(backend::remove_template(name, loc)?.into())
+                            backend::remove_template(name.clone(), loc).map(QueryResult::from)
                         },
                         Token::Instance => {
-                            Ok(backend::remove_instance(name, loc)?.into())
+                            backend::remove_instance(name.clone(), loc).map(QueryResult::from)
                         },
                         _ => Err(PangError::ExecutionError),
                     },
                     _ => Err(PangError::ExecutionError),
+                };
+                // IF EXISTS
+                match res {
+                    Ok(_) if child.is_some() => Ok(QueryResult::Action { action: Action::DELETED, name }),
+                    Err(PangError::TemplateNonExistent(missing, _) | PangError::InstanceNonExistent(missing, _))
+                        if child.is_some() && missing == name => Ok(QueryResult::Action { action: Action::SKIPPED, name }),
+                    res => res,
                 }
             },
             _ => Err(PangError::ExecutionError),
@@ -221,9 +507,45 @@ fn delete_statement(context: Node, child: Option<Box<Node>>, loc: usize) -> Resu
     }?])
 }
 
+/// Deletes every instance of a template, that matches the `WHERE` condition
+fn delete_instances(child: Option<Box<Node>>, loc: usize) -> Result<Vec<QueryResult>, PangError> {
+    // OF "template" <WHERE ...|CONFIRM> COUNT
+    let mut nodes = child.map(|child| child.flatten()).unwrap_or_default();
+    let count = matches!(nodes.last(), Some(Node::Token(Token::Count, _)));
+    if count {
+        nodes.pop();
+    }
+    let name = match nodes.get(..2) {
+        Some([Node::Token(Token::Of, _), Node::Literal(name, _)]) => name.clone(),
+        _ => return Err(PangError::ExecutionError),
+    };
+    let condition = match nodes.get(2) {
+        Some(Node::Token(Token::Where, _)) => Some(create_condition(nodes.split_off(3))?),
+        Some(Node::Token(Token::Confirm, _)) => None,
+        _ => return Err(PangError::UnconfirmedDelete(name, loc)),
+    };
+    let removed = backend::remove_instances(name, condition, loc)?;
+    Ok(match count {
+        true => vec![QueryResult::Count(removed.len())],
+        false => removed.into_iter().map(QueryResult::from).collect(),
+    })
+}
+
+/// Adds or removes the ordered index of fields
+fn index_statement(child: Option<Box<Node>>, indexed: bool, loc: usize) -> Result<(), PangError> {
+    // ON "template" ("field", ...)
+    match child.map(|child| child.flatten()).as_deref() {
+        Some([Node::Token(Token::On, _), Node::Literal(name, _), Node::List(fields, _)]) =>
+            backend::index_fields(name.clone(), fields.clone(), indexed, loc),
+        _ => Err(PangError::ExecutionError),
+    }
+}
+
 /// Query the parsed data from memory
-pub fn data(lines: Vec<Vec<TokenMatch>>) -> String {
-    let ast = ast::parse(lines);
+/// 
+/// The placeholders of the lines are bound to the params first.
+pub fn data(lines: Vec<Vec<TokenMatch>>, params: &serde_json::Value) -> String {
+    let ast = lexer::bind(lines, params).and_then(ast::parse);
     match ast {
         Ok(ast) => match exec(ast) {
             Ok(res) => res,
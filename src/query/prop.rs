@@ -1,7 +1,21 @@
-use crate::{ast::Node, error::PangError, lexer::data::Token, data::serialization::{Data, DataType}};
+use crate::{ast::Node, error::PangError, lexer::data::Token, data::{serialization::{Data, DataType}, structure::{FieldOptions, Reference, OnDelete, Pattern, Template}, expression::{Expression, Operator}, condition::{Condition, Comparison, TextMatch}}};
+
+use std::{iter::Peekable, vec::IntoIter};
+
+use super::backend;
+
+/// Converts a value node to [`Data`] and its location
+fn create_value(node: Node) -> Result<(Data, usize), PangError> {
+    match node {
+        Node::Literal(string, loc) => Ok((string.into(), loc)),
+        Node::Int(int, loc) => Ok((int.into(), loc)),
+        Node::Float(float, loc) => Ok((float.into(), loc)),
+        _ => Err(PangError::ExecutionError),
+    }
+}
 
 /// Creates a Variable from the Inside branch of a Template Shell
-pub fn create_template_prop(prop: Node) -> Result<(String, Data), PangError> {
+pub fn create_template_prop(template: &String, prop: Node) -> Result<(String, Data, FieldOptions), PangError> {
     // Check if node is statement
     match prop {
         Node::Statement { variant, context, child } => {
@@ -11,8 +25,16 @@ pub fn create_template_prop(prop: Node) -> Result<(String, Data), PangError> {
                     Token::StringType => DataType::STRING,
                     Token::IntegerType => DataType::INTEGER,
                     Token::FloatType => DataType::FLOAT,
+                    Token::Enum => return create_enum_prop(*context, child),
                     _ => return Err(PangError::SyntaxError(loc))
                 },
+                // Field embedding or referencing another template
+                Node::Literal(name, _) => return match *context {
+                    Node::Token(Token::Type, _) => create_nested_prop(template, name, child),
+                    Node::Token(Token::Ref, _) => create_reference_prop(template, name, child),
+                    Node::Token(_, loc) => Err(PangError::SyntaxError(loc)),
+                    _ => Err(PangError::ExecutionError),
+                },
                 _ => return Err(PangError::ExecutionError),
             };
 
@@ -22,116 +44,351 @@ pub fn create_template_prop(prop: Node) -> Result<(String, Data), PangError> {
                 _ => return Err(PangError::ExecutionError),
             }?;
 
-            // Actual data check if starting value is given
-            let data: Data = match child {
-                Some(child) => {
-                    // Verify that child is a statement
-                    match *child {
-                        Node::Statement { variant, context, child } => {
-                            // Validate, that variant is VALUE
-                            match *variant {
-                                Node::Token(token, loc) => match token {
-                                    Token::Value => {}
-                                    _ => return Err(PangError::SyntaxError(loc))
-                                },
-                                _ => return Err(PangError::ExecutionError),
-                            };
-                            
-                            // If child is something throw it out the window
-                            if child.is_some() {
-                                return create_template_prop(*child.unwrap())
-                            }
-
-                            // Validate value
-                            let data: (Data, usize) = match *context {
-                                Node::Literal(string, loc) => (string.into(), loc),
-                                Node::Int(int, loc) => (int.into(), loc),
-                                Node::Float(float, loc) => (float.into(), loc),
-                                _ => return Err(PangError::ExecutionError),
-                            };
-
-                            // Validate types
-                            if data_type != data.0.data_type {
-                                return Err(PangError::TypeMismatch(data.1))
-                            }
-
-                            Ok(data.0)
-                        },
+            // Starting value and constraints following the name
+            let mut data: Option<(Data, usize)> = None;
+            let mut options = FieldOptions::default();
+            let mut loc = 0;
+            let mut nodes = child.map(|c| c.flatten()).unwrap_or_default().into_iter();
+            while let Some(node) = nodes.next() {
+                let token = match node {
+                    Node::Token(token, token_loc) => {
+                        loc = token_loc;
+                        token
+                    },
+                    _ => return Err(PangError::ExecutionError),
+                };
+                let numeric = data_type == DataType::INTEGER || data_type == DataType::FLOAT;
+                match token {
+                    Token::Value => data = Some(create_value(nodes.next().ok_or(PangError::ExecutionError)?)?),
+                    Token::Min | Token::Max if numeric => {
+                        let (value, _) = create_value(nodes.next().ok_or(PangError::ExecutionError)?)?;
+                        match token {
+                            Token::Min => options.min = Some(value),
+                            _ => options.max = Some(value),
+                        }
+                    },
+                    Token::MaxLen if data_type == DataType::STRING => match nodes.next() {
+                        Some(Node::Int(max_len, _)) => options.max_len = Some(max_len.max(0) as usize),
                         _ => return Err(PangError::ExecutionError),
-                    }?
-                },
-                None => match data_type {
+                    },
+                    Token::Matches if data_type == DataType::STRING => match nodes.next() {
+                        Some(Node::Literal(pattern, loc)) => options.matches = Some(Pattern::new(&pattern, loc)?),
+                        _ => return Err(PangError::ExecutionError),
+                    },
+                    Token::Not if data_type == DataType::STRING => {
+                        nodes.next();
+                        options.not_empty = true
+                    },
+                    Token::Unique => options.unique = true,
+                    Token::Searchable if data_type == DataType::STRING => options.searchable = true,
+                    Token::Starting => options.starting = Some(create_value(nodes.next().ok_or(PangError::ExecutionError)?)?.0),
+                    Token::As => options.computed = Some(create_expression(nodes.by_ref().collect())?),
+                    // Constraint doesn't apply to the DataType
+                    _ => return Err(PangError::TypeMismatch(loc)),
+                }
+            }
+
+            let (data, loc) = match data {
+                Some(data) => data,
+                None => (match data_type {
                     DataType::STRING => "".into(),
                     DataType::INTEGER => 0.into(),
                     DataType::FLOAT => 0.0.into(),
-                },
+                    DataType::OBJECT => return Err(PangError::ExecutionError),
+                }, loc),
             };
 
-            return Ok((name, data))
+            // Validate types
+            if data_type != data.data_type || options.starting.as_ref().is_some_and(|s| s.data_type != data_type) {
+                return Err(PangError::TypeMismatch(loc))
+            }
+            options.validate(&name, &data, loc)?;
+            if let Some(starting) = &options.starting {
+                options.validate(&name, starting, loc)?;
+            }
+
+            Ok((name, data, options))
         },
         _ => return Err(PangError::ExecutionError),
     }
 }
 
-/// Creates a Variable from the Inside branch of a Select Shell
-pub fn create_select_prop(prop: Node) -> Result<(String, (Data, usize)), PangError> {
-    // Check if node is statement
+/// Creates an [`Expression`] from alternating operands and operators, `*` and `/` bind stronger than `+` and `-`
+fn create_expression(nodes: Vec<Node>) -> Result<Expression, PangError> {
+    let operation = |left, operator, right| Expression::Operation(Box::new(left), operator, Box::new(right));
+    let mut nodes = nodes.into_iter();
+    // The sum so far with its pending operator and the current product
+    let mut sum: Option<(Expression, Operator)> = None;
+    let mut product = create_operand(nodes.next())?;
+    while let Some(node) = nodes.next() {
+        let operator = match node {
+            Node::Token(Token::Plus, _) => Operator::ADD,
+            Node::Token(Token::Minus, _) => Operator::SUB,
+            Node::Token(Token::Star, _) => Operator::MUL,
+            Node::Token(Token::Slash, _) => Operator::DIV,
+            _ => return Err(PangError::ExecutionError),
+        };
+        let operand = create_operand(nodes.next())?;
+        match operator {
+            Operator::MUL | Operator::DIV => product = operation(product, operator, operand),
+            _ => {
+                let left = match sum {
+                    Some((left, pending)) => operation(left, pending, product),
+                    None => product,
+                };
+                sum = Some((left, operator));
+                product = operand;
+            },
+        }
+    }
+    Ok(match sum {
+        Some((left, pending)) => operation(left, pending, product),
+        None => product,
+    })
+}
+
+/// Converts an operand node to a field or a constant value
+fn create_operand(node: Option<Node>) -> Result<Expression, PangError> {
+    match node {
+        Some(Node::Literal(field, _)) => Ok(Expression::Field(field)),
+        Some(node) => Ok(Expression::Value(create_value(node)?.0)),
+        None => Err(PangError::ExecutionError),
+    }
+}
+
+/// Creates a [`Condition`] from comparisons joined by `AND` and `OR`, `AND` binds stronger than `OR`
+pub fn create_condition(nodes: Vec<Node>) -> Result<Condition, PangError> {
+    let mut nodes = nodes.into_iter().peekable();
+    // The disjunction so far and the current conjunction
+    let mut any: Option<Condition> = None;
+    let mut all = create_comparison(&mut nodes)?;
+    while let Some(node) = nodes.next() {
+        let comparison = create_comparison(&mut nodes)?;
+        match node {
+            Node::Token(Token::And, _) => all = Condition::And(Box::new(all), Box::new(comparison)),
+            Node::Token(Token::Or, _) => {
+                any = Some(match any {
+                    Some(any) => Condition::Or(Box::new(any), Box::new(all)),
+                    None => all,
+                });
+                all = comparison;
+            },
+            _ => return Err(PangError::ExecutionError),
+        }
+    }
+    Ok(match any {
+        Some(any) => Condition::Or(Box::new(any), Box::new(all)),
+        None => all,
+    })
+}
+
+/// Converts a field or `NAME`, a comparison and a value node to a [`Condition`]
+fn create_comparison(nodes: &mut Peekable<IntoIter<Node>>) -> Result<Condition, PangError> {
+    let field = match nodes.next() {
+        Some(Node::Literal(field, _)) => Some(field),
+        Some(Node::Token(Token::Name, _)) => None,
+        _ => return Err(PangError::ExecutionError),
+    };
+    let comparison = match nodes.next() {
+        Some(Node::Token(Token::Equal, _)) => Comparison::EQ,
+        Some(Node::Token(Token::NotEqual, _)) => Comparison::NE,
+        Some(Node::Token(Token::Less, _)) => Comparison::LT,
+        Some(Node::Token(Token::Greater, _)) => Comparison::GT,
+        Some(Node::Token(Token::LessEqual, _)) => Comparison::LE,
+        Some(Node::Token(Token::GreaterEqual, _)) => Comparison::GE,
+        Some(Node::Token(token, _)) => return create_text_match(field, token, nodes),
+        _ => return Err(PangError::ExecutionError),
+    };
+    let value = create_value(nodes.next().ok_or(PangError::ExecutionError)?)?.0;
+    Ok(Condition::Compare(field, comparison, value))
+}
+
+/// Converts a string pattern with an optional `IGNORE CASE` to a [`Condition`]
+fn create_text_match(field: Option<String>, token: Token, nodes: &mut Peekable<IntoIter<Node>>) -> Result<Condition, PangError> {
+    let text_match = match token {
+        Token::Like => TextMatch::LIKE,
+        Token::Contains => TextMatch::CONTAINS,
+        Token::Matches => TextMatch::MATCHES,
+        Token::Starts => match nodes.next() {
+            Some(Node::Token(Token::With, _)) => TextMatch::STARTS,
+            _ => return Err(PangError::ExecutionError),
+        },
+        _ => return Err(PangError::ExecutionError),
+    };
+    let (pattern, loc) = match nodes.next() {
+        Some(Node::Literal(pattern, loc)) => (pattern, loc),
+        Some(node) => return Err(PangError::TypeMismatch(create_value(node)?.1)),
+        None => return Err(PangError::ExecutionError),
+    };
+    let ignore_case = matches!(nodes.peek(), Some(Node::Token(Token::Ignore, _)));
+    if ignore_case {
+        match (nodes.next(), nodes.next()) {
+            (_, Some(Node::Token(Token::Case, _))) => {},
+            _ => return Err(PangError::ExecutionError),
+        }
+    }
+    Ok(Condition::Text(field, text_match, text_match.compile(&pattern, ignore_case, loc)?))
+}
+
+/// Creates a string Variable restricted to a list of values, the first one is the default
+fn create_enum_prop(context: Node, child: Option<Box<Node>>) -> Result<(String, Data, FieldOptions), PangError> {
+    // Name of the field
+    let name = match context {
+        Node::Literal(name, _) => name,
+        _ => return Err(PangError::ExecutionError),
+    };
+
+    // OF (...) VALUE ... STARTING ...
+    let mut nodes = child.map(|c| c.flatten()).unwrap_or_default().into_iter().skip(1);
+    let (allowed, loc) = match nodes.next() {
+        Some(Node::List(allowed, loc)) => (allowed, loc),
+        _ => return Err(PangError::ExecutionError),
+    };
+    let mut default = None;
+    let mut starting = None;
+    while let (Some(Node::Token(token, _)), Some(Node::Literal(value, loc))) = (nodes.next(), nodes.next()) {
+        match token {
+            Token::Value => default = Some((value, loc)),
+            _ => starting = Some((value, loc)),
+        }
+    }
+
+    let (default, loc) = default.unwrap_or((allowed.first().cloned().unwrap_or_default(), loc));
+    let data: Data = default.into();
+    let options = FieldOptions { allowed: Some(allowed), ..Default::default() };
+    options.validate(&name, &data, loc)?;
+    let options = match starting {
+        Some((starting, loc)) => {
+            let starting: Data = starting.into();
+            options.validate(&name, &starting, loc)?;
+            FieldOptions { starting: Some(starting), ..options }
+        },
+        None => options,
+    };
+    Ok((name, data, options))
+}
+
+/// Creates a Variable embedding another [`Template`](crate::data::structure::Template) with its default values
+fn create_nested_prop(template: &str, name: String, child: Option<Box<Node>>) -> Result<(String, Data, FieldOptions), PangError> {
+    // Name of the nested template
+    let (nested, loc) = match child.map(|c| *c) {
+        Some(Node::Literal(nested, loc)) => (nested, loc),
+        _ => return Err(PangError::ExecutionError),
+    };
+
+    backend::check_nesting(template, &nested, loc)?;
+    let data = backend::copy_template(nested.clone(), loc)?.data.into();
+    Ok((name, data, FieldOptions { template: Some(nested), ..Default::default() }))
+}
+
+/// Creates a Variable holding the name of an instance of another [`Template`](crate::data::structure::Template)
+fn create_reference_prop(template: &String, name: String, child: Option<Box<Node>>) -> Result<(String, Data, FieldOptions), PangError> {
+    // Name of the referenced template and what happens on deletion
+    let (referenced, on_delete, loc) = match child.map(|c| *c) {
+        Some(Node::Literal(referenced, loc)) => (referenced, OnDelete::RESTRICT, loc),
+        Some(Node::Statement { variant, context: _, child }) => {
+            let (referenced, loc) = match *variant {
+                Node::Literal(referenced, loc) => (referenced, loc),
+                _ => return Err(PangError::ExecutionError),
+            };
+            // ON DELETE <RESTRICT|CASCADE|SET NULL>
+            let on_delete = match child.map(|c| *c) {
+                Some(Node::Statement { variant: _, context, child: _ }) => match *context {
+                    Node::Token(Token::Restrict, _) => OnDelete::RESTRICT,
+                    Node::Token(Token::Cascade, _) => OnDelete::CASCADE,
+                    Node::Token(Token::Set, _) => OnDelete::NULL,
+                    Node::Token(_, loc) => return Err(PangError::SyntaxError(loc)),
+                    _ => return Err(PangError::ExecutionError),
+                },
+                _ => return Err(PangError::ExecutionError),
+            };
+            (referenced, on_delete, loc)
+        },
+        _ => return Err(PangError::ExecutionError),
+    };
+
+    // A template may reference its own instances
+    if &referenced != template {
+        backend::copy_template(referenced.clone(), loc)?;
+    }
+    let options = FieldOptions { reference: Some(Reference { template: referenced, on_delete }), ..Default::default() };
+    Ok((name, "".into(), options))
+}
+
+/// Reads the field name from a GET statement of a Select Shell
+pub fn create_get_prop(prop: Node) -> Result<(String, usize), PangError> {
     match prop {
-        Node::Statement { variant, context, child } => {
-            // Check if Set token is present
+        Node::Statement { variant, context, child: _ } => {
+            // Check if Get token is present
             match *variant {
                 Node::Token(token, loc) => match token {
-                    Token::Set => {},
+                    Token::Get => {},
                     _ => return Err(PangError::SyntaxError(loc))
                 },
                 _ => return Err(PangError::ExecutionError),
             };
- 
+
             // Name of the field
-            let (name, loc) = match *context {
+            match *context {
                 Node::Literal(string, loc) => Ok((string, loc)),
-                _ => return Err(PangError::ExecutionError),
-            }?;
- 
-            // Actual data check if starting value is given
-            let data = match child {
-                Some(child) => {
-                    // Verify that child is a statement
-                    match *child {
-                        Node::Statement { variant, context, child } => {
-                            // Validate, that variant is VALUE
-                            match *variant {
-                                Node::Token(token, loc) => match token {
-                                    // Validate VALUE token
-                                    Token::Value => {}
-                                    _ => return Err(PangError::SyntaxError(loc))
-                                },
-                                _ => return Err(PangError::ExecutionError),
-                            };
-                         
-                            // If child is something throw it out the window
-                            if child.is_some() {
-                                return create_select_prop(*child.unwrap())
-                            }
- 
-                            // Validate value
-                            let data: (Data, usize) = match *context {
-                                Node::Literal(string, loc) => (string.into(), loc),
-                                Node::Int(int, loc) => (int.into(), loc),
-                                Node::Float(float, loc) => (float.into(), loc),
-                                _ => return Err(PangError::ExecutionError),
-                            };
- 
-                            Ok(data)
-                        },
-                        _ => return Err(PangError::ExecutionError),
-                    }?
-                },
-                None => return Err(PangError::SyntaxError(loc + name.len() + 3)),
-            };
-            return Ok((name, data))
+                _ => Err(PangError::ExecutionError),
+            }
         },
-        _ => return Err(PangError::ExecutionError),
+        _ => Err(PangError::ExecutionError),
     }
 }
+
+/// Creates a Variable from the Inside branch of a Select Shell
+/// 
+/// The new value is an [`Expression`], so it can depend on the current values of the instance.
+pub fn create_select_prop(template: &Template, prop: Node) -> Result<(String, (Expression, usize)), PangError> {
+    // SET "name" <VALUE|ADD|MUL|APPEND> value ...
+    let mut nodes = prop.flatten().into_iter();
+    match nodes.next() {
+        Some(Node::Token(Token::Set, _)) => {},
+        Some(Node::Token(_, loc)) => return Err(PangError::SyntaxError(loc)),
+        _ => return Err(PangError::ExecutionError),
+    };
+
+    // Name of the field
+    let (name, loc) = match nodes.next() {
+        Some(Node::Literal(string, loc)) => (string, loc),
+        _ => return Err(PangError::ExecutionError),
+    };
+
+    let (operator, op_loc) = match nodes.next() {
+        Some(Node::Token(token, loc)) => (token, loc),
+        _ => return Err(PangError::SyntaxError(loc + name.len() + 3)),
+    };
+    let value = nodes.next().ok_or(PangError::SyntaxError(op_loc))?;
+    let rest: Vec<Node> = nodes.collect();
+    let field = || Box::new(Expression::Field(name.clone()));
+    let expression = match (operator, value, rest.is_empty()) {
+        (Token::Value, Node::Token(Token::Default, loc), true) => Expression::Value(template.starting(&name, loc)?),
+        (Token::Value, value, true) => Expression::Value(create_value(value)?.0),
+        // Operands of an expression are fields, like in a computed field
+        (Token::Value, value, false) => {
+            let mut nodes = vec![value];
+            nodes.extend(rest);
+            create_expression(nodes)?
+        },
+        (Token::Add | Token::Mul, value, true) => {
+            let (data, loc) = create_value(value)?;
+            if data.as_f64().is_none() {
+                return Err(PangError::TypeMismatch(loc))
+            }
+            let operator = if operator == Token::Add { Operator::ADD } else { Operator::MUL };
+            Expression::Operation(field(), operator, Box::new(Expression::Value(data)))
+        },
+        (Token::Append, value, true) => {
+            let (data, loc) = create_value(value)?;
+            if data.as_str().is_none() {
+                return Err(PangError::TypeMismatch(loc))
+            }
+            Expression::Operation(field(), Operator::ADD, Box::new(Expression::Value(data)))
+        },
+        _ => return Err(PangError::SyntaxError(op_loc)),
+    };
+    Ok((name, (expression, loc)))
+}
  
\ No newline at end of file
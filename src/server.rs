@@ -1,5 +1,7 @@
 use std::{future::Future};
 
+use serde::Deserialize;
+use serde_json::Value;
 use tokio::{net::{TcpListener, TcpStream}, io::{AsyncWriteExt}};
 
 use crate::{lexer::{self}, query::{self}, perr, plog, backup::SaveWorker, crypto::Key};
@@ -7,6 +9,15 @@ use crate::{lexer::{self}, query::{self}, perr, plog, backup::SaveWorker, crypto
 type Error = Box<dyn std::error::Error + Send + Sync>;
 static BUFFER_SIZE: usize = 1048576;
 
+/// Request frame of a query with values bound to its placeholders
+#[derive(Deserialize)]
+struct Request {
+    query: String,
+    /// List of the values of `$1`, `$2`, ... or object of the values of `:name`
+    #[serde(default)]
+    params: Value,
+}
+
 struct Server {
     listener: TcpListener,
     aes_key: Vec<u8>,
@@ -24,8 +35,13 @@ impl Server {
         let source = String::from_utf8(data)?;
         // Removing trailing padding 0's from decrypted query
         let source = source.trim_matches(char::from(0)).to_string();
+        // Queries with placeholders are sent together with their values
+        let (source, params) = match serde_json::from_str::<Request>(&source) {
+            Ok(request) => (request.query, request.params),
+            Err(_) => (source, Value::Null),
+        };
         let l = lexer::parse(source);
-        let result = query::data(l);
+        let result = query::data(l, &params);
         self.send(stream, result).await?;
         stream.shutdown().await?;
         Ok(())